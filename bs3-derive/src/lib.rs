@@ -0,0 +1,372 @@
+//!
+//! Derive macros for bs3
+//!
+//! `#[derive(State)]` on a struct of models generates:
+//!
+//! * `{Name}Storage<S, M>`: one `SnapshotableStorage` per field over a shared backend,
+//!   namespaced by `{Name}.{field}`, with a single `commit`, `rollback`, `root`,
+//!   `export_snapshot`, backup, restore and commit hooks.
+//! * `{Name}Transaction<'a, S, M>`: one `Transaction` per field, implementing `Forkable`
+//!   with the struct itself as cache.
+//! * `{Name}Changes`: typed operations of a commit by field, given to commit hooks.
+//!   Fields must implement `Changes`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+#[proc_macro_derive(State)]
+pub fn derive_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_state(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_state(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "State can't be derived for generic struct",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) if !fields.named.is_empty() => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "State needs a struct with named model fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "State can only be derived for struct",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let storage = format_ident!("{}Storage", name);
+    let transaction = format_ident!("{}Transaction", name);
+    let changes = format_ident!("{}Changes", name);
+
+    let idents: Vec<_> = fields.iter().filter_map(|f| f.ident.as_ref()).collect();
+    let field_vis: Vec<_> = fields.iter().map(|f| &f.vis).collect();
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let namespaces: Vec<_> = idents.iter().map(|i| i.to_string()).collect();
+    let staged: Vec<_> = idents.iter().map(|i| format_ident!("__{}", i)).collect();
+    let name_str = name.to_string();
+    let first = idents[0];
+
+    // Same as the root of a snapshot over all fields.
+    let root = if idents.len() == 1 {
+        quote! { self.#first.root() }
+    } else {
+        quote! {
+            use ::bs3::digest::Digest;
+
+            let mut hasher = <M as ::bs3::merkle::Merkle>::Digest::new();
+            #(hasher.update(self.#idents.root()?);)*
+            Ok(hasher.finalize())
+        }
+    };
+
+    let storage_doc = format!("Snapshotable storages of [`{}`].", name);
+    let transaction_doc = format!("Transaction over [`{}`].", storage);
+    let changes_doc = format!("Typed operations of a commit of [`{}`], by field.", storage);
+
+    Ok(quote! {
+        #[doc = #changes_doc]
+        #[derive(Debug, Clone)]
+        #vis struct #changes {
+            #(#field_vis #idents: ::bs3::__private::Vec<::bs3::model::Change<#types>>,)*
+        }
+
+        #[doc = #storage_doc]
+        #vis struct #storage<S, M>
+        where
+            S: ::bs3::Store,
+            M: ::bs3::merkle::Merkle,
+        {
+            #(#field_vis #idents: ::bs3::SnapshotableStorage<S, M, #types>,)*
+            __validators: ::bs3::__private::Vec<
+                ::bs3::__private::Arc<
+                    dyn Fn(i64, &#storage<S, M>, &#changes) -> ::bs3::Result<()> + Send + Sync,
+                >,
+            >,
+            __listeners: ::bs3::__private::Vec<
+                ::bs3::__private::Arc<
+                    dyn Fn(
+                            i64,
+                            &::bs3::digest::Output<<M as ::bs3::merkle::Merkle>::Digest>,
+                            &#changes,
+                        ) + Send
+                        + Sync,
+                >,
+            >,
+        }
+
+        impl<S, M> #storage<S, M>
+        where
+            S: ::bs3::Store,
+            M: ::bs3::merkle::Merkle,
+        {
+            /// Create or load storages, each field uses `{Name}.{field}` as namespace.
+            pub fn new(store: S) -> ::bs3::Result<Self> {
+                Self::new_with_name(#name_str, store)
+            }
+
+            /// Create or load storages, each field uses `{name}.{field}` as namespace.
+            ///
+            /// Fails with `Error::StateHeightMismatch` if the fields are at different heights.
+            pub fn new_with_name(name: &str, store: S) -> ::bs3::Result<Self> {
+                let storage = Self {
+                    #(#idents: ::bs3::SnapshotableStorage::new_with_name(
+                        <#types as ::core::default::Default>::default(),
+                        [name, ".", #namespaces].concat(),
+                        ::core::clone::Clone::clone(&store),
+                    )?,)*
+                    __validators: ::bs3::__private::Vec::new(),
+                    __listeners: ::bs3::__private::Vec::new(),
+                };
+                #(if storage.#idents.height != storage.height() {
+                    return Err(::bs3::Error::StateHeightMismatch {
+                        namespace: ::bs3::__private::String::from(storage.#idents.namespace()),
+                        expected: storage.height(),
+                        found: storage.#idents.height,
+                    });
+                })*
+                Ok(storage)
+            }
+
+            /// Current height.
+            pub fn height(&self) -> i64 {
+                self.#first.height
+            }
+
+            /// Commit all storages.
+            ///
+            /// Every storage is staged, then validators run and the records of all are
+            /// written in one batch. If one fails the others keep their cache and the ones
+            /// already written are rolled back, an error of the rollback is reported first.
+            /// Listeners of the storages and of the state run once all are written.
+            pub fn commit(&mut self) -> ::bs3::Result<i64> {
+                self.commit_inner(None)
+            }
+
+            /// Commit all storages, each storage records the metadata.
+            pub fn commit_with_metadata(
+                &mut self,
+                metadata: ::bs3::CommitMetadata,
+            ) -> ::bs3::Result<i64> {
+                self.commit_inner(Some(metadata))
+            }
+
+            fn commit_inner(
+                &mut self,
+                metadata: Option<::bs3::CommitMetadata>,
+            ) -> ::bs3::Result<i64> {
+                let height = self.height() + 1;
+
+                #(let mut #staged = None;)*
+                let mut hooked = None;
+                let result = (|| -> ::bs3::Result<()> {
+                    #(#staged = Some(self.#idents.stage_commit(::core::clone::Clone::clone(&metadata))?);)*
+                    let changes = match self.__validators.is_empty() && self.__listeners.is_empty() {
+                        true => None,
+                        false => Some(#changes {
+                            #(#idents: match #staged.as_ref() {
+                                Some(staged) => staged.changes()?,
+                                None => ::bs3::__private::Vec::new(),
+                            },)*
+                        }),
+                    };
+                    if let Some(changes) = changes.as_ref() {
+                        for validator in self.__validators.iter() {
+                            validator(height, self, changes)?;
+                        }
+                    }
+                    let mut batch = ::bs3::__private::Vec::new();
+                    #(if let Some(staged) = #staged.as_ref() {
+                        batch.extend_from_slice(staged.operations());
+                    })*
+                    self.#first.execute_batch(batch)?;
+                    #(if let Some(staged) = #staged.as_mut() {
+                        self.#idents.apply_shared_commit(staged)?;
+                    })*
+                    if let Some(changes) = changes {
+                        if !self.__listeners.is_empty() {
+                            hooked = Some((self.root()?, changes));
+                        }
+                    }
+                    Ok(())
+                })();
+                if let Err(e) = result {
+                    let mut aborted = Ok(());
+                    #(if let Some(staged) = #staged.take() {
+                        let abort = self.#idents.abort_commit(staged);
+                        if aborted.is_ok() {
+                            aborted = abort;
+                        }
+                    })*
+                    aborted?;
+                    return Err(e);
+                }
+
+                #(if let Some(staged) = #staged.as_ref() {
+                    self.#idents.notify_commit(staged);
+                })*
+                if let Some((root, changes)) = hooked {
+                    for listener in self.__listeners.iter() {
+                        listener(height, &root, &changes);
+                    }
+                }
+                Ok(self.height())
+            }
+
+            /// Run `f` with the height and the changes of each commit before it's written,
+            /// an error vetoes it.
+            pub fn with_commit_validator<F>(mut self, f: F) -> Self
+            where
+                F: Fn(i64, &Self, &#changes) -> ::bs3::Result<()> + Send + Sync + 'static,
+            {
+                self.__validators.push(::bs3::__private::Arc::new(f));
+                self
+            }
+
+            /// Run `f` with the height, root and changes after each successful commit.
+            pub fn with_commit_listener<F>(mut self, f: F) -> Self
+            where
+                F: Fn(
+                        i64,
+                        &::bs3::digest::Output<<M as ::bs3::merkle::Merkle>::Digest>,
+                        &#changes,
+                    ) + Send
+                    + Sync
+                    + 'static,
+            {
+                self.__listeners.push(::bs3::__private::Arc::new(f));
+                self
+            }
+
+            /// Rollback all storages to point height.
+            pub fn rollback(&mut self, target_height: i64) -> ::bs3::Result<()> {
+                #(self.#idents.rollback(target_height)?;)*
+                Ok(())
+            }
+
+            /// Hash of all storage roots in field order, or the root of the only field.
+            pub fn root(
+                &self,
+            ) -> ::bs3::Result<::bs3::digest::Output<<M as ::bs3::merkle::Merkle>::Digest>> {
+                #root
+            }
+
+            /// Export the state of all storages at a committed height.
+            pub fn export_snapshot(&self, height: i64) -> ::bs3::Result<::bs3::Snapshot> {
+                let mut writer =
+                    ::bs3::SnapshotWriter::<<M as ::bs3::merkle::Merkle>::Digest>::new(height);
+                #(writer.add(&self.#idents)?;)*
+                writer.finish()
+            }
+
+            /// Copy all versions of all storages up to a committed height into `dest`.
+            pub fn backup_to<B: ::bs3::Store>(&self, dest: &mut B, height: i64) -> ::bs3::Result<()> {
+                #(self.#idents.backup_to(dest, height)?;)*
+                Ok(())
+            }
+
+            /// Copy versions of all storages above `from_height` into a backup at `from_height`.
+            pub fn backup_incremental_to<B: ::bs3::Store>(
+                &self,
+                dest: &mut B,
+                from_height: i64,
+                height: i64,
+            ) -> ::bs3::Result<()> {
+                #(self.#idents.backup_incremental_to(dest, from_height, height)?;)*
+                Ok(())
+            }
+
+            /// Copy backups of all storages into an empty `store`, then load them and check the root.
+            pub fn restore<B: ::bs3::Store>(backup: &B, store: S, root: &[u8]) -> ::bs3::Result<Self> {
+                Self::restore_with_name(#name_str, backup, store, root)
+            }
+
+            /// Like `restore`, for storages created by `new_with_name`.
+            pub fn restore_with_name<B: ::bs3::Store>(
+                name: &str,
+                backup: &B,
+                mut store: S,
+                root: &[u8],
+            ) -> ::bs3::Result<Self> {
+                #(::bs3::restore_backup(backup, &mut store, &[name, ".", #namespaces].concat())?;)*
+                Self::new_with_name(name, store)?.verify_root(root)
+            }
+
+            fn verify_root(self, root: &[u8]) -> ::bs3::Result<Self> {
+                if self.root()?[..] != root[..] {
+                    return Err(::bs3::Error::BackupRootMismatch {
+                        height: self.height(),
+                    });
+                }
+                Ok(self)
+            }
+
+            /// Generate transaction for all storages.
+            pub fn transaction(&self) -> #transaction<'_, S, M> {
+                #transaction::new(self)
+            }
+
+            /// Consume transaction cache to apply.
+            pub fn execute(&mut self, cache: #name) {
+                #(self.#idents.execute(cache.#idents);)*
+            }
+        }
+
+        #[doc = #transaction_doc]
+        #[derive(Clone)]
+        #vis struct #transaction<'a, S, M>
+        where
+            S: ::bs3::Store,
+            M: ::bs3::merkle::Merkle,
+        {
+            #(#field_vis #idents: ::bs3::Transaction<'a, S, M, #types>,)*
+        }
+
+        impl<'a, S, M> #transaction<'a, S, M>
+        where
+            S: ::bs3::Store,
+            M: ::bs3::merkle::Merkle,
+        {
+            pub fn new(storage: &'a #storage<S, M>) -> Self {
+                Self {
+                    #(#idents: ::bs3::Transaction::new(&storage.#idents),)*
+                }
+            }
+        }
+
+        impl<'a, S, M> ::bs3::Forkable for #transaction<'a, S, M>
+        where
+            S: ::bs3::Store,
+            M: ::bs3::merkle::Merkle,
+        {
+            type Cache = #name;
+
+            fn cache(self) -> #name {
+                #name {
+                    #(#idents: self.#idents.value,)*
+                }
+            }
+
+            fn merge(&mut self, cache: #name) {
+                #(self.#idents.execute(cache.#idents);)*
+            }
+        }
+    })
+}
@@ -0,0 +1,174 @@
+//!
+//! Trait AsyncStore is the storage layer for async runtimes
+//! Reads return owned records, since they are sent back from other threads
+//!
+//! `BlockingStore` turns any `Store` into an `AsyncStore`, calls run on the
+//! blocking pool of tokio and must be made inside a tokio runtime.
+//!
+//! `AsyncBackend` turns an `AsyncStore` back into a `Store` for storages run on the
+//! blocking pool, like `AsyncStorage`.
+//!
+
+use core::future::Future;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use std::sync::RwLock;
+
+use tokio::runtime::Handle;
+
+use crate::{CowBytes, Error, Result};
+
+use super::Store;
+
+pub trait AsyncStore: Send + Sync + Clone {
+    /// Provide this method to range key.
+    fn range(
+        &self,
+        begin_key: &[u8],
+        end_key: &[u8],
+    ) -> impl Future<Output = Result<Vec<(Vec<u8>, Vec<u8>)>>> + Send;
+
+    /// Provide this method to execute transaction.
+    fn execute(
+        &mut self,
+        batch: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Provide this method to remove keys, missing keys are skipped.
+    ///
+    /// Only stores built `with_pruning` remove, the default fails.
+    fn remove(&mut self, _keys: Vec<Vec<u8>>) -> impl Future<Output = Result<()>> + Send {
+        async { Err(Error::Unsupported("remove")) }
+    }
+
+    /// Get value by exact key.
+    fn get(&self, key: &[u8]) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send {
+        async move {
+            let mut value = self.range(key, key).await?;
+            Ok(value.pop().map(|(_, v)| v))
+        }
+    }
+}
+
+/// `AsyncStore` running a `Store` on the blocking pool.
+///
+/// Clones share the store, reads run concurrently and writes exclusively.
+pub struct BlockingStore<S: Store> {
+    inner: Arc<RwLock<S>>,
+}
+
+impl<S: Store> Clone for BlockingStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: Store> BlockingStore<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(store)),
+        }
+    }
+}
+
+impl<S: Store + 'static> AsyncStore for BlockingStore<S> {
+    async fn range(&self, begin_key: &[u8], end_key: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let inner = self.inner.clone();
+        let (begin_key, end_key) = (begin_key.to_vec(), end_key.to_vec());
+        blocking(move || {
+            let store = inner.read().unwrap_or_else(|e| e.into_inner());
+            let records = store
+                .range(&begin_key, &end_key)?
+                .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                .collect();
+            Ok(records)
+        })
+        .await
+    }
+
+    async fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        let inner = self.inner.clone();
+        blocking(move || {
+            let mut store = inner.write().unwrap_or_else(|e| e.into_inner());
+            store.execute(batch)
+        })
+        .await
+    }
+
+    async fn remove(&mut self, keys: Vec<Vec<u8>>) -> Result<()> {
+        let inner = self.inner.clone();
+        blocking(move || {
+            let mut store = inner.write().unwrap_or_else(|e| e.into_inner());
+            store.remove(keys)
+        })
+        .await
+    }
+}
+
+/// `Store` waiting on an `AsyncStore`.
+///
+/// Calls block on the current tokio runtime, they must be made on its blocking pool
+/// and fail outside a runtime.
+pub struct AsyncBackend<A: AsyncStore> {
+    inner: A,
+}
+
+impl<A: AsyncStore> Clone for AsyncBackend<A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A: AsyncStore> AsyncBackend<A> {
+    pub fn new(store: A) -> Self {
+        Self { inner: store }
+    }
+
+    fn block_on<F: Future>(future: F) -> Result<F::Output> {
+        let handle = Handle::try_current().map_err(|e| Error::BackendIo(Box::new(e)))?;
+        Ok(handle.block_on(future))
+    }
+}
+
+impl<A: AsyncStore> Store for AsyncBackend<A> {
+    type Range<'a>
+        = alloc::vec::IntoIter<(CowBytes<'a>, CowBytes<'a>)>
+    where
+        Self: 'a;
+
+    fn range(&self, begin_key: &[u8], end_key: &[u8]) -> Result<Self::Range<'_>> {
+        let records = Self::block_on(self.inner.range(begin_key, end_key))??;
+        Ok(records
+            .into_iter()
+            .map(|(k, v)| (CowBytes::Owned(k), CowBytes::Owned(v)))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        Self::block_on(self.inner.execute(batch))?
+    }
+
+    fn remove(&mut self, keys: Vec<Vec<u8>>) -> Result<()> {
+        Self::block_on(self.inner.remove(keys))?
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
+        Ok(Self::block_on(self.inner.get(key))??.map(CowBytes::Owned))
+    }
+}
+
+/// Run `f` on the blocking pool, a panic of `f` is returned as error.
+pub(crate) async fn blocking<F, R>(f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::BackendIo(Box::new(e)))?
+}
@@ -96,6 +96,11 @@ impl Store for MemoryBackend {
         })
     }
 
+    /// Point lookup
+    fn get(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
+        Ok(self.cache.get(key).map(CowBytes::Borrowed))
+    }
+
     /// Batch insert
     fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
         let inner = &mut self.cache;
@@ -104,4 +109,12 @@ impl Store for MemoryBackend {
         }
         Ok(())
     }
+
+    /// Batch remove
+    fn remove(&mut self, keys: Vec<Vec<u8>>) -> Result<()> {
+        for key in keys {
+            self.cache.remove(&key);
+        }
+        Ok(())
+    }
 }
@@ -5,12 +5,19 @@
 mod store;
 pub use store::Store;
 
+#[cfg(feature = "async")]
+pub mod async_store;
+#[cfg(feature = "async")]
+pub use async_store::{AsyncBackend, AsyncStore, BlockingStore};
+
 #[cfg(feature = "sled-backend")]
 pub mod sled;
 #[cfg(feature = "sled-backend")]
 pub use self::sled::sled_db_open;
 #[cfg(feature = "sled-backend")]
 pub use self::sled::SledBackend;
+#[cfg(feature = "sled-backend")]
+pub use self::sled::SledConfig;
 
 // pub mod helper;
 
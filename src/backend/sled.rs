@@ -9,7 +9,6 @@ use sled::{Db, Iter, Tree};
 use crate::{CowBytes, Error, Result};
 
 use super::Store;
-use alloc::string::ToString;
 use core::ops::{Bound, RangeBounds};
 
 ///
@@ -17,10 +16,7 @@ use core::ops::{Bound, RangeBounds};
 #[derive(Clone)]
 pub struct SledBackend {
     tree: Tree,
-}
-
-fn e(e: sled::Error) -> Error {
-    Error::StoreError(Box::new(e))
+    read_only: bool,
 }
 
 ///
@@ -36,50 +32,183 @@ pub fn tmp_dir() -> Result<std::path::PathBuf> {
     Ok(path)
 }
 
-/// create sled db
-/// feat Compression
-pub fn sled_db_open(path: Option<&str>) -> Result<sled::Db> {
-    let mut is_tmp = false;
+pub use sled::Mode;
+
+/// Options to open sled db
+///
+/// Defaults match `sled_db_open`.
+#[derive(Debug, Clone)]
+pub struct SledConfig {
+    path: Option<std::path::PathBuf>,
+    mode: Mode,
+    cache_capacity: u64,
+    flush_every_ms: Option<u64>,
+    compression_factor: Option<i32>,
+    temporary: bool,
+    read_only: bool,
+}
 
-    let path = if let Some(path) = path {
-        path.to_string()
-    } else if let Some(path) = tmp_dir()?.to_str() {
-        is_tmp = true;
-        path.to_string()
-    } else {
-        return Err(Error::StoreError(Box::new("tmp_dir is none")));
-    };
+impl Default for SledConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            mode: Mode::HighThroughput,
+            cache_capacity: 20_000_000,
+            flush_every_ms: Some(3000),
+            #[cfg(feature = "compress")]
+            compression_factor: Some(15),
+            #[cfg(not(feature = "compress"))]
+            compression_factor: None,
+            temporary: false,
+            read_only: false,
+        }
+    }
+}
 
-    let mut cfg = sled::Config::default()
-        .path(path)
-        .mode(sled::Mode::HighThroughput)
-        .cache_capacity(20_000_000)
-        .flush_every_ms(Some(3000));
+impl SledConfig {
+    /// create default config
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Db path, use a new dir in temp dir if not set.
+    pub fn path<P: AsRef<std::path::Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
 
-    if is_tmp {
-        cfg = cfg.temporary(true);
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
     }
 
-    #[cfg(feature = "compress")]
-    let cfg = cfg.use_compression(true).compression_factor(15);
+    /// Page cache size in bytes.
+    pub fn cache_capacity(mut self, cache_capacity: u64) -> Self {
+        self.cache_capacity = cache_capacity;
+        self
+    }
+
+    /// Background flush interval, `None` to flush manually.
+    pub fn flush_every_ms(mut self, flush_every_ms: Option<u64>) -> Self {
+        self.flush_every_ms = flush_every_ms;
+        self
+    }
+
+    /// Zstd compression level, `None` to disable compression.
+    pub fn compression_factor(mut self, compression_factor: Option<i32>) -> Self {
+        self.compression_factor = compression_factor;
+        self
+    }
+
+    /// Remove db files on drop.
+    pub fn temporary(mut self, temporary: bool) -> Self {
+        self.temporary = temporary;
+        self
+    }
+
+    /// Open an existing db without background flush.
+    ///
+    /// The returned db still accepts writes, open trees with
+    /// `SledBackend::open_tree_read_only` to reject them.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// open sled db
+    pub fn open(&self) -> Result<Db> {
+        let mut is_tmp = self.temporary;
+
+        let path = match &self.path {
+            Some(path) => {
+                if self.read_only && !path.exists() {
+                    let e = std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        std::format!("db path {} not found", path.display()),
+                    );
+                    return Err(Error::BackendIo(Box::new(e)));
+                }
+                path.clone()
+            }
+            None if self.read_only => {
+                let e = std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "read only db needs a path",
+                );
+                return Err(Error::BackendIo(Box::new(e)));
+            }
+            None => {
+                is_tmp = true;
+                tmp_dir()?
+            }
+        };
+
+        let flush_every_ms = if self.read_only {
+            None
+        } else {
+            self.flush_every_ms
+        };
+
+        let mut cfg = sled::Config::default()
+            .path(path)
+            .mode(self.mode)
+            .cache_capacity(self.cache_capacity)
+            .flush_every_ms(flush_every_ms)
+            .temporary(is_tmp);
+
+        if let Some(factor) = self.compression_factor {
+            cfg = cfg.use_compression(true).compression_factor(factor);
+        }
+
+        Ok(cfg.open()?)
+    }
+}
 
-    Ok(cfg.open()?)
+/// create sled db
+/// feat Compression
+pub fn sled_db_open(path: Option<&str>) -> Result<sled::Db> {
+    let cfg = SledConfig::default();
+    match path {
+        Some(path) => cfg.path(path).open(),
+        None => cfg.open(),
+    }
 }
 
 impl SledBackend {
     /// create tree
     pub fn open_tree(db: &Db, namespace: &str) -> Result<Self> {
-        let tree = db.open_tree(namespace).map_err(e)?;
-        Ok(Self { tree })
+        let tree = db.open_tree(namespace)?;
+        Ok(Self {
+            tree,
+            read_only: false,
+        })
+    }
+
+    /// open an existing tree which rejects writes
+    ///
+    /// Missing trees are not created.
+    pub fn open_tree_read_only(db: &Db, namespace: &str) -> Result<Self> {
+        if !db
+            .tree_names()
+            .iter()
+            .any(|name| name == namespace.as_bytes())
+        {
+            let e = std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                std::format!("tree {} not found", namespace),
+            );
+            return Err(Error::BackendIo(Box::new(e)));
+        }
+        let tree = db.open_tree(namespace)?;
+        Ok(Self {
+            tree,
+            read_only: true,
+        })
     }
 
     /// get
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        match self.tree.get(key) {
-            Ok(Some(v)) => Ok(Some(v.to_vec())),
-            Ok(None) => Ok(None),
-            Err(e) => Err(Error::StoreError(Box::new(e))),
-        }
+        Ok(self.tree.get(key)?.map(|v| v.to_vec()))
     }
 
     pub fn flush(&self) -> Result<()> {
@@ -149,8 +278,17 @@ impl Store for SledBackend {
         })
     }
 
+    /// Point lookup
+    fn get(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
+        Ok(self.tree.get(key)?.map(|v| CowBytes::Owned(v.to_vec())))
+    }
+
     /// Batch insert
     fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
         let inner = &mut self.tree;
         log::debug!("Write {} record", batch.len());
         for (key, value) in batch {
@@ -160,6 +298,19 @@ impl Store for SledBackend {
         }
         Ok(())
     }
+
+    /// Batch remove
+    fn remove(&mut self, keys: Vec<Vec<u8>>) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        log::debug!("Remove {} record", keys.len());
+        for key in keys {
+            self.tree.remove(key)?;
+        }
+        Ok(())
+    }
 }
 
 // #[test]
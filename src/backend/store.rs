@@ -4,30 +4,37 @@
 //! where the type specifies the return of the range
 //!
 
-use crate::{CowBytes, Result};
+use crate::{CowBytes, Error, Result};
 use alloc::{vec, vec::Vec};
 
 pub trait Store: Send + Sync + Clone {
-    #[cfg(feature = "nightly")]
     type Range<'a>: DoubleEndedIterator<Item = (CowBytes<'a>, CowBytes<'a>)>
     where
         Self: 'a;
 
-    #[cfg(feature = "nightly")]
     /// Provide this method to range key.
     fn range(&self, begin_key: &[u8], end_key: &[u8]) -> Result<Self::Range<'_>>;
 
     /// Provide this method to execute transaction.
     fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()>;
 
+    /// Provide this method to remove keys, missing keys are skipped.
+    ///
+    /// Only stores built `with_pruning` remove, the default fails.
+    fn remove(&mut self, _keys: Vec<Vec<u8>>) -> Result<()> {
+        Err(Error::Unsupported("remove"))
+    }
+
     fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         self.execute(vec![(key, value)])
     }
 
-    #[cfg(not(feature = "nightly"))]
-    fn get_ge(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>>;
+    /// Get value by exact key.
+    fn get(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
+        let mut value = self.range(key, key)?;
+        Ok(value.next().map(|(_, v)| v))
+    }
 
-    #[cfg(feature = "nightly")]
     fn get_ge(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
         let mut value = self.range(&Vec::new(), key)?;
         Ok(match value.next_back() {
@@ -41,7 +48,6 @@ pub trait Store: Send + Sync + Clone {
 
     /// This is the upgraded version of get_ge
     /// The main thing is that the start index is not a fixed empty vec
-    #[cfg(feature = "nightly")]
     fn get_ge2(&self, keys: (&[u8], &[u8])) -> Result<Option<CowBytes<'_>>> {
         let mut value = self.range(keys.0, keys.1)?;
         Ok(match value.next_back() {
@@ -0,0 +1,130 @@
+//!
+//! Check integrity of a bs3 database on sled
+//!
+//! Usage: bs3-fsck <path> [--tree <name>] [--merkle none|sha3-256|sha3-512] [--json]
+//!
+//! All trees of the db are checked unless `--tree` is given. Roots are computed with
+//! the append only merkle of the digest given by `--merkle`, `sha3-256` by default.
+//! Exits with 1 if a problem is found, 2 if the db can't be read.
+//!
+
+use std::{env, process};
+
+use bs3::{
+    backend::{SledBackend, SledConfig},
+    merkle::{append_only::AppendOnlyMerkle, empty::EmptyMerkle},
+    verify, Result, VerifyReport,
+};
+use serde::Serialize;
+use sha3::{Sha3_256, Sha3_512};
+
+const USAGE: &str =
+    "usage: bs3-fsck <path> [--tree <name>] [--merkle none|sha3-256|sha3-512] [--json]";
+
+struct Args {
+    path: String,
+    tree: Option<String>,
+    merkle: String,
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct TreeReport {
+    tree: String,
+    #[serde(flatten)]
+    report: VerifyReport,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut args = env::args().skip(1);
+    let mut path = None;
+    let mut tree = None;
+    let mut merkle = "sha3-256".to_string();
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tree" => tree = Some(args.next()?),
+            "--merkle" => merkle = args.next()?,
+            "--json" => json = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return None,
+        }
+    }
+
+    Some(Args {
+        path: path?,
+        tree,
+        merkle,
+        json,
+    })
+}
+
+fn verify_tree(store: &SledBackend, merkle: &str) -> Option<Result<VerifyReport>> {
+    Some(match merkle {
+        "none" => verify::<_, EmptyMerkle<Sha3_256>>(store),
+        "sha3-256" => verify::<_, AppendOnlyMerkle<Sha3_256>>(store),
+        "sha3-512" => verify::<_, AppendOnlyMerkle<Sha3_512>>(store),
+        _ => return None,
+    })
+}
+
+fn run(args: &Args) -> Result<bool> {
+    let db = SledConfig::new().path(&args.path).read_only(true).open()?;
+    let trees = match &args.tree {
+        Some(tree) => vec![tree.clone()],
+        None => db
+            .tree_names()
+            .iter()
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect(),
+    };
+
+    let mut ok = true;
+    for tree in trees {
+        let store = SledBackend::open_tree_read_only(&db, &tree)?;
+        let report = match verify_tree(&store, &args.merkle) {
+            Some(report) => report?,
+            None => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        };
+        ok &= report.is_ok();
+
+        if args.json {
+            println!("{}", serde_json::to_string(&TreeReport { tree, report })?);
+            continue;
+        }
+        for namespace in report.namespaces.iter() {
+            println!(
+                "{}: namespace {:?} at height {}, {} records, {} roots checked",
+                tree, namespace.namespace, namespace.height, namespace.records, namespace.roots
+            );
+        }
+        for problem in report.problems.iter() {
+            println!("{}: {}", tree, problem);
+        }
+    }
+
+    Ok(ok)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Some(args) => args,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("bs3-fsck: {}", e);
+            process::exit(2);
+        }
+    }
+}
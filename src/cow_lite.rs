@@ -18,7 +18,7 @@ impl<'a, T> Deref for Cow<'a, T> {
     fn deref(&self) -> &Self::Target {
         match self {
             Cow::Owned(v) => v,
-            Cow::Borrowed(v) => *v,
+            Cow::Borrowed(v) => v,
         }
     }
 }
@@ -27,7 +27,7 @@ impl<'a, T> AsRef<T> for Cow<'a, T> {
     fn as_ref(&self) -> &T {
         match self {
             Cow::Owned(t) => t,
-            Cow::Borrowed(t) => *t,
+            Cow::Borrowed(t) => t,
         }
     }
 }
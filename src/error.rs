@@ -1,11 +1,123 @@
-use core::{cell, fmt::Debug};
+use core::{
+    cell,
+    fmt::{self, Debug, Display},
+};
 
 use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::vec::Vec;
 
 #[derive(Debug)]
 pub enum Error {
-    StoreError(Box<dyn Debug>),
+    /// Other error reported by a store implementation.
+    StoreError(Box<dyn Debug + Send + Sync>),
+
+    /// Key is not found in store.
+    KeyNotFound {
+        key: Vec<u8>,
+    },
+
+    /// Record in store can't be decoded.
+    Corrupted {
+        key: Vec<u8>,
+        reason: String,
+    },
+
+    /// Requested height is above current height.
+    HeightOutOfRange {
+        requested: i64,
+        current: i64,
+    },
+
+    /// Expire height is below the height being built, so the entry would never be visible.
+    ExpireHeightPassed {
+        expire_height: i64,
+        current: i64,
+    },
+
+    /// Snapshot manifest or chunk can't be applied.
+    InvalidSnapshot(String),
+
+    /// Roots of a snapshot manifest don't match its root, or the merkle records
+    /// carried by its chunks.
+    SnapshotRootMismatch,
+
+    /// Snapshot is imported into a backend which already has the namespace.
+    StoreNotEmpty {
+        namespace: String,
+    },
+
+    /// Storage of a composite state is at another height than its first storage.
+    StateHeightMismatch {
+        namespace: String,
+        expected: i64,
+        found: i64,
+    },
+
+    /// Incremental backup is applied on a backup at another height.
+    BackupHeightMismatch {
+        expected: i64,
+        found: i64,
+    },
+
+    /// Restored root doesn't match the expected root.
+    BackupRootMismatch {
+        height: i64,
+    },
+
+    /// Commit is vetoed by a validator.
+    CommitRejected(String),
+
+    /// Access is aborted by the meter of a transaction.
+    MeterExhausted(String),
+
+    /// Fork is not registered or was dropped.
+    ForkNotFound {
+        name: String,
+    },
+
+    /// Fork with this name is already registered.
+    ForkExists {
+        name: String,
+    },
+
+    /// Rollback target is below the height a live fork was created at.
+    ForkedAbove {
+        name: String,
+        height: i64,
+    },
+
+    /// When you load a store with another model.
+    TypeMissMatch {
+        expected: u32,
+        expected_name: &'static str,
+        found: u32,
+        found_name: &'static str,
+    },
+
+    /// Namespace was written with another key layout, namespaces of an older layout
+    /// are upgraded by `SnapshotableStorage::migrate_key_layout`.
+    LayoutMismatch {
+        namespace: String,
+        expected: u32,
+        found: u32,
+    },
+
+    /// Type code is reserved or registered by another model.
+    TypeCodeConflict {
+        code: u32,
+        name: &'static str,
+    },
+
+    /// Type name is used by a built-in model or registered with another code.
+    TypeNameConflict {
+        name: &'static str,
+        code: u32,
+    },
+
+    /// Backend failed to read or write.
+    #[cfg(feature = "std")]
+    BackendIo(Box<dyn std::error::Error + Send + Sync>),
 
     #[cfg(feature = "cbor")]
     CborDeError(ciborium::de::Error<core::convert::Infallible>),
@@ -18,26 +130,178 @@ pub enum Error {
     #[cfg(feature = "cbor")]
     CborSerIoError(String),
 
-    HeightError,
+    /// Key can't be written with the ordered key encoding.
+    #[cfg(feature = "cbor")]
+    KeyEncodeError(String),
+    /// Key bytes are not a valid ordered key encoding.
+    #[cfg(feature = "cbor")]
+    KeyDecodeError(String),
+
     BorrowMutError(cell::BorrowMutError),
     BorrowError(cell::BorrowError),
     LockReadError,
-    /// When you load a store.
-    TypeMissMatch,
+    LockWriteError,
+    /// Write to a backend opened as read only.
+    ReadOnly,
+    /// Commit, rollback or fork of a view returned by `at`.
+    ReadOnlyView {
+        height: i64,
+    },
+    /// Operation is not implemented by the backend.
+    Unsupported(&'static str),
+    /// Counter value overflows its type.
+    Overflow,
+
+    #[cfg(feature = "json")]
+    JsonError(serde_json::Error),
+}
 
-    #[cfg(feature = "sled-backend")]
-    SledError(sled::Error),
+impl Error {
+    /// Attach the key of the record which failed to decode.
+    pub(crate) fn with_key(self, key: &[u8]) -> Self {
+        match self {
+            #[cfg(feature = "cbor")]
+            Error::CborDeIoError(reason) => Error::Corrupted {
+                key: key.to_vec(),
+                reason,
+            },
+            #[cfg(feature = "cbor")]
+            Error::KeyDecodeError(reason) => Error::Corrupted {
+                key: key.to_vec(),
+                reason,
+            },
+            e => e,
+        }
+    }
+}
 
-    #[cfg(feature = "sled-backend")]
-    StdIoError(std::io::Error),
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::StoreError(e) => write!(f, "store error: {:?}", e),
+            Error::KeyNotFound { key } => write!(f, "key not found: {}", hex::encode(key)),
+            Error::Corrupted { key, reason } => {
+                write!(f, "corrupted record at {}: {}", hex::encode(key), reason)
+            }
+            Error::HeightOutOfRange { requested, current } => write!(
+                f,
+                "height {} out of range, current height is {}",
+                requested, current
+            ),
+            Error::ExpireHeightPassed {
+                expire_height,
+                current,
+            } => write!(
+                f,
+                "expire height {} already passed, current height is {}",
+                expire_height, current
+            ),
+            Error::InvalidSnapshot(e) => write!(f, "invalid snapshot: {}", e),
+            Error::SnapshotRootMismatch => write!(f, "snapshot root mismatch"),
+            Error::StoreNotEmpty { namespace } => {
+                write!(f, "namespace {} is not empty", namespace)
+            }
+            Error::StateHeightMismatch {
+                namespace,
+                expected,
+                found,
+            } => write!(
+                f,
+                "storage {} is at height {}, expected {}",
+                namespace, found, expected
+            ),
+            Error::BackupHeightMismatch { expected, found } => write!(
+                f,
+                "backup height mismatch, expected {} but found {}",
+                expected, found
+            ),
+            Error::BackupRootMismatch { height } => {
+                write!(f, "restored root mismatch at height {}", height)
+            }
+            Error::CommitRejected(e) => write!(f, "commit rejected: {}", e),
+            Error::MeterExhausted(e) => write!(f, "meter exhausted: {}", e),
+            Error::ForkNotFound { name } => write!(f, "fork {} not found", name),
+            Error::ForkExists { name } => write!(f, "fork {} already exists", name),
+            Error::ForkedAbove { name, height } => write!(
+                f,
+                "fork {} was created at height {} above the rollback target",
+                name, height
+            ),
+            Error::TypeMissMatch {
+                expected,
+                expected_name,
+                found,
+                found_name,
+            } => write!(
+                f,
+                "store type mismatch, expected {}({}) but found {}({})",
+                expected_name, expected, found_name, found
+            ),
+            Error::LayoutMismatch {
+                namespace,
+                expected,
+                found,
+            } => write!(
+                f,
+                "namespace {} uses key layout {}, expected {}",
+                namespace, found, expected
+            ),
+            Error::TypeCodeConflict { code, name } => {
+                write!(f, "type code {} is already used by {}", code, name)
+            }
+            Error::TypeNameConflict { name, code } => {
+                write!(f, "type name {} is already used by code {}", name, code)
+            }
+            #[cfg(feature = "std")]
+            Error::BackendIo(e) => write!(f, "backend io error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborDeError(e) => write!(f, "cbor decode error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborSerError(e) => write!(f, "cbor encode error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborDeIoError(e) => write!(f, "cbor decode error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborSerIoError(e) => write!(f, "cbor encode error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::KeyEncodeError(e) => write!(f, "key encode error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::KeyDecodeError(e) => write!(f, "key decode error: {}", e),
+            Error::BorrowMutError(e) => write!(f, "{}", e),
+            Error::BorrowError(e) => write!(f, "{}", e),
+            Error::LockReadError => write!(f, "failed to acquire read lock"),
+            Error::LockWriteError => write!(f, "failed to acquire write lock"),
+            Error::ReadOnly => write!(f, "backend is read only"),
+            Error::ReadOnlyView { height } => write!(f, "view at height {} is read only", height),
+            Error::Unsupported(op) => write!(f, "backend doesn't support {}", op),
+            Error::Overflow => write!(f, "counter overflow"),
+            #[cfg(feature = "json")]
+            Error::JsonError(e) => write!(f, "json error: {}", e),
+        }
+    }
+}
 
-    JsonError(serde_json::Error),
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::BackendIo(e) => Some(e.as_ref()),
+            #[cfg(feature = "cbor")]
+            Error::CborDeError(e) => Some(e),
+            #[cfg(feature = "cbor")]
+            Error::CborSerError(e) => Some(e),
+            Error::BorrowMutError(e) => Some(e),
+            Error::BorrowError(e) => Some(e),
+            #[cfg(feature = "json")]
+            Error::JsonError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
-#[cfg(feature = "sled-backend")]
+#[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
-        self::Error::StdIoError(e)
+        self::Error::BackendIo(Box::new(e))
     }
 }
 
@@ -55,6 +319,7 @@ impl From<ciborium::ser::Error<core::convert::Infallible>> for Error {
     }
 }
 
+#[cfg(feature = "json")]
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
         Error::JsonError(e)
@@ -76,7 +341,7 @@ impl From<cell::BorrowError> for Error {
 #[cfg(feature = "sled-backend")]
 impl From<sled::Error> for Error {
     fn from(e: sled::Error) -> Self {
-        self::Error::SledError(e)
+        self::Error::BackendIo(Box::new(e))
     }
 }
 
@@ -0,0 +1,187 @@
+//!
+//! Order preserving key encoding
+//!
+//! Keys of `Map`, `Vec`, `DoubleKeyMap`, `Set` and `Deque` are written with a
+//! memcomparable encoding, the byte order of encoded keys equals the logical
+//! order of keys, so backend iteration returns them sorted:
+//!
+//! * bool, unsigned integer, char: fixed width big endian.
+//! * signed integer, float: big endian with sign bit flipped.
+//! * string, bytes: `0x00` escaped as `0x00 0xff`, terminated by `0x00 0x00`.
+//! * option: `0x00` for none, `0x01` followed by value for some.
+//! * tuple, struct: concatenation of fields.
+//! * enum: variant index as u32 big endian followed by fields.
+//! * seq, map: every item is preceded by `0x01`, terminated by `0x00`.
+//!
+//! Every encoding is prefix free, so an encoded tuple starts with the encoding
+//! of its first item. Types with a hand written `Ord` which disagrees with
+//! their serde layout don't keep their order.
+
+use core::fmt::{self, Display};
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+mod de;
+mod ser;
+
+/// Key which can be encoded into bytes with the same order.
+///
+/// Implemented for every serde type, including integers, strings, byte arrays,
+/// tuples and enums.
+pub trait OrderedKey: Sized {
+    /// Encode key into memcomparable bytes.
+    fn to_key_bytes(&self) -> Result<Vec<u8>>;
+
+    /// Decode key from bytes produced by `to_key_bytes`.
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self>;
+}
+
+impl<T> OrderedKey for T
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    fn to_key_bytes(&self) -> Result<Vec<u8>> {
+        let mut serializer = ser::KeySerializer { out: Vec::new() };
+        self.serialize(&mut serializer)
+            .map_err(|e| Error::KeyEncodeError(e.0))?;
+        Ok(serializer.out)
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut deserializer = de::KeyDeserializer { input: bytes };
+        let key = T::deserialize(&mut deserializer).map_err(|e| Error::KeyDecodeError(e.0))?;
+        if !deserializer.input.is_empty() {
+            return Err(Error::KeyDecodeError(String::from("trailing bytes")));
+        }
+        Ok(key)
+    }
+}
+
+/// Error of key serializer, converted to `Error` at the boundary.
+#[derive(Debug)]
+pub(crate) struct KeyError(String);
+
+impl From<&str> for KeyError {
+    fn from(msg: &str) -> Self {
+        Self(String::from(msg))
+    }
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl serde::ser::StdError for KeyError {}
+
+impl serde::ser::Error for KeyError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl serde::de::Error for KeyError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    use super::OrderedKey;
+
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+    enum Kind {
+        Unit,
+        Id(u32),
+        Named { name: String, index: i16 },
+    }
+
+    fn assert_sorted<T: OrderedKey + Ord + Clone + core::fmt::Debug>(keys: &[T]) {
+        for pair in keys.windows(2) {
+            assert!(pair[0] < pair[1]);
+            let a = pair[0].to_key_bytes().unwrap();
+            let b = pair[1].to_key_bytes().unwrap();
+            assert!(a < b, "{:?} {:?}", pair[0], pair[1]);
+        }
+        for key in keys {
+            let bytes = key.to_key_bytes().unwrap();
+            assert_eq!(&T::from_key_bytes(&bytes).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_ordered_key() {
+        assert_sorted(&[i64::MIN, -256, -1, 0, 1, 255, 256, i64::MAX]);
+        assert_sorted(&[0u64, 1, 255, 256, u64::MAX]);
+        assert_sorted(&[i8::MIN, -1, 0, i8::MAX]);
+        assert_sorted(&[
+            String::new(),
+            String::from("\0"),
+            String::from("\0\0"),
+            String::from("a"),
+            String::from("a\0"),
+            String::from("a\x01"),
+            String::from("ab"),
+            String::from("b"),
+        ]);
+        assert_sorted(&[
+            vec![],
+            vec![0u8],
+            vec![0, 0],
+            vec![0, 1],
+            vec![1],
+            vec![255],
+        ]);
+        assert_sorted(&[
+            (0u8, String::from("z")),
+            (1, String::new()),
+            (1, String::from("a")),
+        ]);
+        assert_sorted(&[
+            (String::from("a"), 2u64),
+            (String::from("a\0"), 1),
+            (String::from("ab"), 0),
+        ]);
+        assert_sorted(&[None, Some(0u32), Some(1)]);
+        assert_sorted(&[
+            Kind::Unit,
+            Kind::Id(0),
+            Kind::Id(7),
+            Kind::Named {
+                name: String::from("a"),
+                index: -1,
+            },
+            Kind::Named {
+                name: String::from("a"),
+                index: 3,
+            },
+            Kind::Named {
+                name: String::from("b"),
+                index: i16::MIN,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_ordered_key_prefix() {
+        let prefix = 7u32.to_key_bytes().unwrap();
+        let pair = (7u32, String::from("x")).to_key_bytes().unwrap();
+        assert!(pair.starts_with(&prefix));
+
+        let bytes: Vec<u8> = 1u32.to_key_bytes().unwrap();
+        assert!(u64::from_key_bytes(&bytes).is_err());
+        assert!(u8::from_key_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+//!
+//! Memcomparable serializer
+//!
+
+use alloc::vec::Vec;
+
+use serde::ser::{self, Serialize};
+
+use super::KeyError;
+
+/// Write `bytes` escaped, `0x00` becomes `0x00 0xff` and `0x00 0x00` ends it.
+fn write_escaped(out: &mut Vec<u8>, bytes: &[u8]) {
+    for b in bytes {
+        if *b == 0 {
+            out.extend_from_slice(&[0x00, 0xff]);
+        } else {
+            out.push(*b);
+        }
+    }
+    out.extend_from_slice(&[0x00, 0x00]);
+}
+
+pub(crate) struct KeySerializer {
+    pub(crate) out: Vec<u8>,
+}
+
+impl KeySerializer {
+    fn write_variant(&mut self, variant_index: u32) {
+        self.out.extend_from_slice(&variant_index.to_be_bytes());
+    }
+}
+
+impl ser::Serializer for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), KeyError> {
+        self.out.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), KeyError> {
+        self.serialize_u8((v as u8) ^ (1 << 7))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), KeyError> {
+        self.serialize_u16((v as u16) ^ (1 << 15))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), KeyError> {
+        self.serialize_u32((v as u32) ^ (1 << 31))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), KeyError> {
+        self.serialize_u64((v as u64) ^ (1 << 63))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), KeyError> {
+        self.serialize_u128((v as u128) ^ (1 << 127))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), KeyError> {
+        self.out.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), KeyError> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), KeyError> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), KeyError> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), KeyError> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    /// Positive values get sign bit set, negative values are inverted.
+    fn serialize_f32(self, v: f32) -> Result<(), KeyError> {
+        let bits = v.to_bits();
+        let bits = if bits >> 31 == 1 {
+            !bits
+        } else {
+            bits ^ (1 << 31)
+        };
+        self.serialize_u32(bits)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), KeyError> {
+        let bits = v.to_bits();
+        let bits = if bits >> 63 == 1 {
+            !bits
+        } else {
+            bits ^ (1 << 63)
+        };
+        self.serialize_u64(bits)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), KeyError> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), KeyError> {
+        write_escaped(&mut self.out, v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), KeyError> {
+        write_escaped(&mut self.out, v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), KeyError> {
+        self.out.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), KeyError> {
+        self.out.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), KeyError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), KeyError> {
+        self.write_variant(variant_index);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), KeyError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), KeyError> {
+        self.write_variant(variant_index);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, KeyError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, KeyError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, KeyError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, KeyError> {
+        self.write_variant(variant_index);
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, KeyError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, KeyError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, KeyError> {
+        self.write_variant(variant_index);
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Every element is marked by `0x01`, `0x00` ends the sequence.
+impl ser::SerializeSeq for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), KeyError> {
+        self.out.push(1);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        self.out.push(0);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), KeyError> {
+        self.out.push(1);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        self.out.push(0);
+        Ok(())
+    }
+}
+
+/// Fixed length items are concatenated.
+impl ser::SerializeTuple for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut KeySerializer {
+    type Ok = ();
+    type Error = KeyError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), KeyError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), KeyError> {
+        Ok(())
+    }
+}
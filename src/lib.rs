@@ -5,6 +5,9 @@
 //!   * map
 //!   * vec
 //!   * value
+//!   * set
+//!   * deque
+//!   * counter
 //! * snapshot /*Middle layer with transactional operations*/
 //!   * storage
 //!   * transaction
@@ -15,6 +18,12 @@
 //!     * Btree<usize,Operation<V>>
 //!   * value
 //!     * Operation<V>
+//!   * set
+//!     * Btree<K,Operation<()>>
+//!   * deque
+//!     * Btree<i64,Operation<V>>, head, tail
+//!   * counter
+//!     * deltas, applied on commit
 //! * backend /*Storage Layer*/
 //!   * memory
 //!   * sled
@@ -56,7 +65,6 @@
 //!     bytes({name_space}-kw-{hex(4)}-{:00000000000000000003}): bytes(Operation::Update(4)),
 //! }
 //! ```
-#![feature(generic_associated_types)]
 #![no_std]
 
 /// For features and alloc.
@@ -79,14 +87,48 @@ pub mod model;
 pub mod prelude;
 
 mod snapshot;
-pub use snapshot::{utils::merkle_key, Forkable, SnapshotableStorage, Transaction};
+#[cfg(feature = "async")]
+pub use snapshot::AsyncStorage;
+pub use snapshot::{
+    import_snapshot, restore_backup, utils::merkle_key, verify, Access, CommitEvent, CommitInfo,
+    CommitMetadata, Forkable, NamespaceReport, NamespaceStats, PendingCommit, Problem, ProblemKind,
+    Snapshot, SnapshotChunks, SnapshotImporter, SnapshotManifest, SnapshotWriter,
+    SnapshotableStorage, Transaction, TxMetrics, VerifyReport, SNAPSHOT_CHUNK_SIZE,
+    SNAPSHOT_FORMAT,
+};
+#[cfg(feature = "std")]
+pub use snapshot::{SharedStorage, StorageReader};
 
 pub mod backend;
+#[cfg(feature = "async")]
+pub use backend::AsyncStore;
 pub use backend::Store;
 
 mod store;
-pub use store::{DoubleKeyMapStore, MapStore, ValueStore, VecStore};
+pub use store::{
+    CounterStore, DequeStore, DoubleKeyMapStore, Index, MapEntry, MapStore, SetStore, ValueStore,
+    VecStore,
+};
 
 mod utils;
 
+#[cfg(feature = "cbor")]
+pub mod key;
+#[cfg(feature = "cbor")]
+pub use key::OrderedKey;
+
 pub mod merkle;
+
+/// Digest used by merkle roots.
+pub use digest;
+
+#[cfg(feature = "derive")]
+pub use bs3_derive::State;
+
+/// Used by `#[derive(State)]`.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::snapshot::staged::StagedCommit;
+    pub use alloc::{string::String, sync::Arc, vec::Vec};
+}
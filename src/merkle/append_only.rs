@@ -1,8 +1,7 @@
 use crate::snapshot::utils::merkle_key;
-use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use digest::generic_array::GenericArray;
 use digest::Digest;
 use digest::Output;
 
@@ -10,7 +9,6 @@ use crate::merkle::value::MerkleValue;
 use crate::snapshot::{FromStoreBytes, ToStoreBytes};
 use crate::{Error, Operation, OperationBytes, Result, Store};
 
-use super::min;
 use super::Merkle;
 
 #[derive(Clone)]
@@ -81,26 +79,23 @@ impl<D: Digest + Clone> Merkle for AppendOnlyMerkle<D> {
         }
 
         if hashs.len() % 2 != 0 {
-            hashs.push(hashs.last().unwrap().clone());
+            if let Some(last) = hashs.last().cloned() {
+                hashs.push(last);
+            }
         }
 
-        let mut num_of_layers = hashs.len();
         let mut offset = 0_usize;
-        while num_of_layers > 1 {
-            let mut left = 0;
-            while left < num_of_layers {
-                let right = min(left + 1, num_of_layers - 1);
-                let left_hash = hashs.get(offset + left).unwrap();
-                let right_hash = hashs.get(offset + right).unwrap();
-                self.hasher.update(left_hash);
-                self.hasher.update(right_hash);
-                let hash = self.hasher.finalize_reset()[..].to_vec();
-                hashs.push(hash);
-
-                left += 2;
+        while hashs.len() - offset > 1 {
+            let layer_end = hashs.len();
+            let mut next_layer = Vec::new();
+            // The last node of an odd layer is paired with itself.
+            for pair in hashs[offset..].chunks(2) {
+                self.hasher.update(&pair[0]);
+                self.hasher.update(&pair[pair.len() - 1]);
+                next_layer.push(self.hasher.finalize_reset()[..].to_vec());
             }
-            offset += num_of_layers;
-            num_of_layers = (num_of_layers + 1) / 2;
+            hashs.append(&mut next_layer);
+            offset = layer_end;
         }
         let operation = Operation::Update(hashs);
         let value = MerkleValue {
@@ -108,7 +103,7 @@ impl<D: Digest + Clone> Merkle for AppendOnlyMerkle<D> {
         };
 
         self.height += 1;
-        let cur_key = merkle_key(&*self.namespace, self.height);
+        let cur_key = merkle_key(&self.namespace, self.height);
         store.insert(cur_key, value.to_bytes()?)?;
 
         Ok(())
@@ -119,25 +114,43 @@ impl<D: Digest + Clone> Merkle for AppendOnlyMerkle<D> {
             return Ok(Default::default());
         }
 
-        let key = merkle_key(&*self.namespace, self.height);
+        let key = merkle_key(&self.namespace, self.height);
         log::debug!("merkle get root key:{:?}", key);
 
         // if get last hash not exist that return default
         if let Some(bytes) = store.get_ge(key.as_slice())? {
             log::debug!("merkle get root value:{:?}", bytes);
 
-            let value = MerkleValue::from_bytes(&bytes)?;
+            let value = MerkleValue::from_bytes(&bytes).map_err(|e| e.with_key(&key))?;
             if let Operation::Update(hashs) =
-                Operation::<Vec<Vec<u8>>>::from_bytes(&value.operation)?
+                Operation::<Vec<Vec<u8>>>::from_bytes(&value.operation)
+                    .map_err(|e| e.with_key(&key))?
             {
                 if let Some(root) = hashs.last() {
-                    let array = GenericArray::<u8, D::OutputSize>::from_slice(root.as_slice());
-                    Ok(array.clone())
+                    let mut array = Output::<D>::default();
+                    if root.len() != array.len() {
+                        return Err(Error::Corrupted {
+                            key,
+                            reason: format!(
+                                "merkle root of {} bytes, digest has {}",
+                                root.len(),
+                                array.len()
+                            ),
+                        });
+                    }
+                    array.copy_from_slice(root.as_slice());
+                    Ok(array)
                 } else {
-                    Err(Error::StoreError(Box::new("this merkle size is 0")))
+                    Err(Error::Corrupted {
+                        key,
+                        reason: "merkle record is empty".to_string(),
+                    })
                 }
             } else {
-                Err(Error::StoreError(Box::new("this operation is delete")))
+                Err(Error::Corrupted {
+                    key,
+                    reason: "merkle record is deleted".to_string(),
+                })
             }
         } else {
             log::debug!("merkle get root value not exist");
@@ -7,7 +7,7 @@ pub mod append_only;
 pub mod empty;
 pub mod sparse_merkle_tree;
 mod utils;
-mod value;
+pub(crate) mod value;
 
 pub use utils::min;
 
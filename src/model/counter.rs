@@ -0,0 +1,154 @@
+//!
+//! counter cache layer
+//!
+use core::fmt::Debug;
+
+use crate::model::{Changes, Model};
+use crate::{Error, Operation, OperationBytes, Result};
+use alloc::vec::Vec;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+/// Number which can be counted by `Counter`.
+pub trait CounterValue:
+    Copy + Default + PartialEq + Debug + Serialize + for<'de> Deserialize<'de>
+{
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_counter_value {
+    ($($t:ty),*) => {
+        $(impl CounterValue for $t {
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+
+            fn checked_sub(self, other: Self) -> Option<Self> {
+                <$t>::checked_sub(self, other)
+            }
+        })*
+    };
+}
+
+impl_counter_value!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+/// define counter, cache keeps deltas instead of value
+///
+/// Merging two caches adds their deltas, so increments from independent
+/// transactions don't conflict. Commit applies the deltas to the latest
+/// committed value.
+#[derive(Debug, Clone, Default)]
+pub struct Counter<N>
+where
+    N: CounterValue,
+{
+    pub(crate) incr: N,
+    pub(crate) decr: N,
+    /// Set when merged deltas overflow, reported on read and commit.
+    pub(crate) overflow: bool,
+    /// Value resolved by `prepare_commit`.
+    pub(crate) value: Option<N>,
+}
+
+impl<N> Counter<N>
+where
+    N: CounterValue,
+{
+    pub(crate) fn add(&mut self, n: N) -> Result<()> {
+        self.incr = self.incr.checked_add(n).ok_or(Error::Overflow)?;
+        Ok(())
+    }
+
+    /// Decrements are accumulated as a positive amount.
+    pub(crate) fn sub(&mut self, n: N) -> Result<()> {
+        self.decr = self.decr.checked_add(n).ok_or(Error::Overflow)?;
+        Ok(())
+    }
+
+    /// Apply deltas to base, increments first.
+    pub(crate) fn apply(&self, base: N) -> Result<N> {
+        if self.overflow {
+            return Err(Error::Overflow);
+        }
+        base.checked_add(self.incr)
+            .and_then(|v| v.checked_sub(self.decr))
+            .ok_or(Error::Overflow)
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.overflow && self.incr == N::default() && self.decr == N::default()
+    }
+}
+
+/// impl model
+impl<N> Model for Counter<N>
+where
+    N: CounterValue,
+{
+    /// Resolve the committed value plus deltas.
+    fn prepare_commit<F>(&mut self, latest: F) -> Result<()>
+    where
+        F: Fn(&[u8]) -> Result<Option<OperationBytes>>,
+    {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let base = match latest(&[])? {
+            Some(operation) => match Operation::from_bytes(&operation)? {
+                Operation::Update(v) => v,
+                Operation::Delete => N::default(),
+            },
+            None => N::default(),
+        };
+        self.value = Some(self.apply(base)?);
+        Ok(())
+    }
+
+    /// Consume the data in the cache
+    /// Also convert key to vec<u8>
+    fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        let mut vec = Vec::new();
+
+        if let Some(value) = self.value.take() {
+            // Empty key.
+            vec.push((Vec::new(), Operation::Update(value).to_bytes()?));
+        }
+        *self = Self::default();
+
+        Ok(vec)
+    }
+
+    /// define type 7
+    fn type_code(&self) -> u32 {
+        super::registry::COUNTER
+    }
+
+    /// Add deltas of two caches
+    fn merge(&mut self, other: Self) {
+        match (
+            self.incr.checked_add(other.incr),
+            self.decr.checked_add(other.decr),
+        ) {
+            (Some(incr), Some(decr)) => {
+                self.incr = incr;
+                self.decr = decr;
+            }
+            _ => self.overflow = true,
+        }
+        self.overflow |= other.overflow;
+    }
+}
+
+impl<N> Changes for Counter<N>
+where
+    N: CounterValue,
+{
+    type Key = ();
+    type Value = N;
+
+    fn decode(_key: &[u8], operation: &OperationBytes) -> Result<Option<((), Operation<N>)>> {
+        Ok(Some(((), Operation::from_bytes(operation)?)))
+    }
+}
@@ -0,0 +1,119 @@
+//!
+//! deque cache layer
+//!
+use core::{fmt::Debug, mem};
+
+use crate::model::{Changes, Model};
+use crate::{Operation, OperationBytes, OrderedKey, Result};
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+/// Key of the persisted head counter, shorter than any encoded index.
+pub(crate) const HEAD_KEY: &[u8] = b"head";
+/// Key of the persisted tail counter, shorter than any encoded index.
+pub(crate) const TAIL_KEY: &[u8] = b"tail";
+
+/// define deque, members are in `[head, tail)`
+///     key : i64
+#[derive(Debug, Clone)]
+pub struct Deque<T>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    pub(crate) value: BTreeMap<i64, Operation<T>>,
+    pub(crate) head: Option<i64>,
+    pub(crate) tail: Option<i64>,
+}
+
+impl<T> Default for Deque<T>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    fn default() -> Self {
+        Self {
+            value: BTreeMap::new(),
+            head: None,
+            tail: None,
+        }
+    }
+}
+
+/// impl model
+impl<T> Model for Deque<T>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    /// Consume the data in the cache
+    /// Also convert key to vec<u8>, counters are written under their name
+    fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        let mut map = Vec::new();
+
+        let value = mem::take(&mut self.value);
+
+        for (k, v) in value.into_iter() {
+            let key = k.to_key_bytes()?;
+            let value = v.to_bytes()?;
+            map.push((key, value));
+        }
+
+        if let Some(head) = self.head.take() {
+            map.push((HEAD_KEY.to_vec(), Operation::Update(head).to_bytes()?));
+        }
+
+        if let Some(tail) = self.tail.take() {
+            map.push((TAIL_KEY.to_vec(), Operation::Update(tail).to_bytes()?));
+        }
+
+        Ok(map)
+    }
+
+    /// define type 6
+    fn type_code(&self) -> u32 {
+        super::registry::DEQUE
+    }
+
+    fn ordered_keys(&self) -> bool {
+        true
+    }
+
+    /// Counters were written under their cbor encoded name.
+    fn upgrade_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        match crate::utils::cbor_decode::<alloc::string::String>(key) {
+            Ok(name) if name.as_bytes() == HEAD_KEY || name.as_bytes() == TAIL_KEY => {
+                Ok(name.into_bytes())
+            }
+            _ => super::upgrade_cbor_key::<i64>(key),
+        }
+    }
+
+    /// Merge two caches
+    fn merge(&mut self, other: Self) {
+        let mut value = other.value;
+        self.value.append(&mut value);
+        if other.head.is_some() {
+            self.head = other.head;
+        }
+        if other.tail.is_some() {
+            self.tail = other.tail;
+        }
+    }
+}
+
+impl<T> Changes for Deque<T>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    type Key = i64;
+    type Value = T;
+
+    /// Head and tail counters are internal records.
+    fn decode(key: &[u8], operation: &OperationBytes) -> Result<Option<(i64, Operation<T>)>> {
+        if key == HEAD_KEY || key == TAIL_KEY {
+            return Ok(None);
+        }
+
+        let index = i64::from_key_bytes(key).map_err(|e| e.with_key(key))?;
+        Ok(Some((index, Operation::from_bytes(operation)?)))
+    }
+}
@@ -5,8 +5,8 @@ use alloc::vec::Vec;
 #[cfg(feature = "cbor")]
 use serde::{Deserialize, Serialize};
 
-use crate::model::{Map, Model};
-use crate::{OperationBytes, Result};
+use crate::model::{Changes, Map, Model};
+use crate::{Operation, OperationBytes, Result};
 
 #[derive(Debug, Clone)]
 pub struct DoubleKeyMap<K1, K2, V>
@@ -38,14 +38,14 @@ where
     V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
 {
     fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
-        use crate::utils::cbor_encode;
+        use crate::OrderedKey;
 
         let mut map = Vec::new();
 
         let value = mem::take(&mut self.value);
 
         for (pair, v) in value.value.into_iter() {
-            let key = cbor_encode(pair)?;
+            let key = pair.to_key_bytes()?;
             let value = v.to_bytes()?;
             map.push((key, value));
         }
@@ -54,7 +54,15 @@ where
     }
 
     fn type_code(&self) -> u32 {
-        4
+        super::registry::DOUBLE_KEY_MAP
+    }
+
+    fn ordered_keys(&self) -> bool {
+        true
+    }
+
+    fn upgrade_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        super::upgrade_cbor_key::<(K1, K2)>(key)
     }
 
     fn merge(&mut self, other: Self) {
@@ -62,3 +70,17 @@ where
         self.value.merge(value)
     }
 }
+
+impl<K1, K2, V> Changes for DoubleKeyMap<K1, K2, V>
+where
+    K1: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    K2: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    type Key = (K1, K2);
+    type Value = V;
+
+    fn decode(key: &[u8], operation: &OperationBytes) -> Result<Option<((K1, K2), Operation<V>)>> {
+        Map::<(K1, K2), V>::decode(key, operation)
+    }
+}
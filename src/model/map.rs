@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{Operation, OperationBytes, Result};
 
-use super::Model;
+use super::{Changes, Model};
 
 ///
 /// define cache map
@@ -24,6 +24,11 @@ where
     V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
 {
     pub(crate) value: BTreeMap<K, Operation<V>>,
+    /// Expire height of keys inserted with ttl.
+    pub(crate) expire: BTreeMap<K, i64>,
+    /// Encoded values read into cache by `entry` and `get_mut`, the keys whose value
+    /// is unchanged are not written.
+    pub(crate) loaded: BTreeMap<K, Vec<u8>>,
 }
 
 impl<K, V> Default for Map<K, V>
@@ -34,6 +39,8 @@ where
     fn default() -> Self {
         Self {
             value: BTreeMap::new(),
+            expire: BTreeMap::new(),
+            loaded: BTreeMap::new(),
         }
     }
 }
@@ -47,21 +54,22 @@ where
 {
     ///define type 3
     fn type_code(&self) -> u32 {
-        3
+        super::registry::MAP
     }
 
     /// Consume the data in the cache
     /// Also convert key to vec<u8>
     #[cfg(feature = "cbor")]
     fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
-        use crate::utils::cbor_encode;
+        use crate::OrderedKey;
 
         let mut map = Vec::new();
 
+        self.drop_unchanged()?;
         let value = mem::take(&mut self.value);
 
         for (k, v) in value.into_iter() {
-            let key = cbor_encode(k)?;
+            let key = k.to_key_bytes()?;
             let value = v.to_bytes()?;
             map.push((key, value));
         }
@@ -69,9 +77,91 @@ where
         Ok(map)
     }
 
+    /// Take expire heights of keys still updated in cache
+    #[cfg(feature = "cbor")]
+    fn expirations(&mut self) -> Result<Vec<(Vec<u8>, i64)>> {
+        use crate::OrderedKey;
+
+        let mut vec = Vec::new();
+
+        for (k, height) in mem::take(&mut self.expire).into_iter() {
+            if let Some(Operation::Update(_)) = self.value.get(&k) {
+                vec.push((k.to_key_bytes()?, height));
+            }
+        }
+
+        Ok(vec)
+    }
+
+    fn ordered_keys(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "cbor")]
+    fn upgrade_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        super::upgrade_cbor_key::<K>(key)
+    }
+
     /// Merge two caches
-    fn merge(&mut self, other: Self) {
+    fn merge(&mut self, mut other: Self) {
+        // Values other read and left unchanged are not writes. If they can't be
+        // encoded they are merged, operations fails on them.
+        #[cfg(feature = "cbor")]
+        let _ = other.drop_unchanged();
+
+        // Writes of other replace expire heights of self.
+        for k in other.value.keys() {
+            self.expire.remove(k);
+            self.loaded.remove(k);
+        }
+        let mut expire = other.expire;
+        self.expire.append(&mut expire);
+
         let mut value = other.value;
         self.value.append(&mut value);
     }
 }
+
+impl<K, V> Map<K, V>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    /// Remember the value of `key` read into cache.
+    #[cfg(feature = "cbor")]
+    pub(crate) fn load(&mut self, key: K, value: V) -> Result<()> {
+        self.loaded
+            .insert(key.clone(), crate::utils::cbor_encode(&value)?);
+        self.value.insert(key, Operation::Update(value));
+        Ok(())
+    }
+
+    /// Remove values read into cache which are still unchanged.
+    #[cfg(feature = "cbor")]
+    fn drop_unchanged(&mut self) -> Result<()> {
+        for (k, bytes) in mem::take(&mut self.loaded) {
+            if let Some(Operation::Update(v)) = self.value.get(&k) {
+                if crate::utils::cbor_encode(v)? == bytes {
+                    self.value.remove(&k);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> Changes for Map<K, V>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    type Key = K;
+    type Value = V;
+
+    fn decode(key: &[u8], operation: &OperationBytes) -> Result<Option<(K, Operation<V>)>> {
+        use crate::OrderedKey;
+
+        let k = K::from_key_bytes(key).map_err(|e| e.with_key(key))?;
+        Ok(Some((k, Operation::from_bytes(operation)?)))
+    }
+}
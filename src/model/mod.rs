@@ -6,7 +6,7 @@ use core::fmt::Debug;
 
 use alloc::vec::Vec as alloc_vec;
 
-use crate::{OperationBytes, Result};
+use crate::{Operation, OperationBytes, Result};
 
 mod value;
 pub use value::Value;
@@ -20,15 +20,85 @@ pub use vec::Vec;
 mod doublekey_map;
 pub use doublekey_map::DoubleKeyMap;
 
+mod set;
+pub use set::Set;
+
+pub(crate) mod deque;
+pub use deque::Deque;
+
+mod counter;
+pub use counter::{Counter, CounterValue};
+
+pub mod registry;
+
 pub trait Model: Default + Debug + Clone {
     /// Get operations for this value.
     ///
     /// Don't forget clean this value to default.
     fn operations(&mut self) -> Result<alloc_vec<(alloc_vec<u8>, OperationBytes)>>;
 
-    /// Define this type's code.
+    /// Define this type's code, see `registry` for reserved codes.
     fn type_code(&self) -> u32;
 
+    /// Human readable name of this type, used in errors.
+    fn type_name(&self) -> &'static str {
+        registry::type_name(self.type_code()).unwrap_or("unknown")
+    }
+
     /// Merge other value.
     fn merge(&mut self, other: Self);
+
+    /// Take expire heights of keys updated in this cache, called on commit before `operations`.
+    ///
+    /// Keys are the ones returned by `operations`.
+    fn expirations(&mut self) -> Result<alloc_vec<(alloc_vec<u8>, i64)>> {
+        Ok(alloc_vec::new())
+    }
+
+    /// Resolve cache against committed state, called on commit before `operations`.
+    ///
+    /// `latest` reads the latest committed operation of a key.
+    fn prepare_commit<F>(&mut self, _latest: F) -> Result<()>
+    where
+        F: Fn(&[u8]) -> Result<Option<OperationBytes>>,
+    {
+        Ok(())
+    }
+
+    /// Keys returned by `operations` depend on the key layout, see `KEY_LAYOUT`.
+    ///
+    /// Namespaces of these models written with an older layout fail to load.
+    fn ordered_keys(&self) -> bool {
+        false
+    }
+
+    /// Re-encode a key written with key layout 0, called by `migrate_key_layout` on
+    /// models with `ordered_keys`.
+    fn upgrade_key(&self, key: &[u8]) -> Result<alloc_vec<u8>> {
+        Ok(key.to_vec())
+    }
+}
+
+/// Re-encode a cbor key of layout 0 with the ordered key encoding.
+#[cfg(feature = "cbor")]
+pub(crate) fn upgrade_cbor_key<K>(key: &[u8]) -> Result<alloc_vec<u8>>
+where
+    K: serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
+    use crate::OrderedKey;
+
+    let key: K = crate::utils::cbor_decode(key).map_err(|e| e.with_key(key))?;
+    key.to_key_bytes()
+}
+
+/// Typed operation of a model.
+pub type Change<V> = (<V as Changes>::Key, Operation<<V as Changes>::Value>);
+
+/// Typed operations of a model, given to commit hooks.
+pub trait Changes: Model {
+    type Key;
+    type Value;
+
+    /// Decode an operation returned by `operations`, `None` for internal records.
+    fn decode(key: &[u8], operation: &OperationBytes) -> Result<Option<Change<Self>>>;
 }
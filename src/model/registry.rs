@@ -0,0 +1,98 @@
+//!
+//! Type code registry
+//!
+//! `Model::type_code` is written under `{namespace}-ty` when a store is created,
+//! and checked when it is loaded again.
+//!
+//! * `1..USER_CODE_START`: reserved for models of bs3, see the constants below.
+//! * `USER_CODE_START..`: free for models defined by other crates. Register the
+//!   code with a name (feature `std`) to catch collisions early and to get the
+//!   name in `Error::TypeMissMatch`.
+
+#[cfg(feature = "std")]
+use crate::{Error, Result};
+
+/// `model::Value`
+pub const VALUE: u32 = 1;
+/// `model::Vec`
+pub const VEC: u32 = 2;
+/// `model::Map`
+pub const MAP: u32 = 3;
+/// `model::DoubleKeyMap`
+pub const DOUBLE_KEY_MAP: u32 = 4;
+/// `model::Set`
+pub const SET: u32 = 5;
+/// `model::Deque`
+pub const DEQUE: u32 = 6;
+/// `model::Counter`
+pub const COUNTER: u32 = 7;
+
+/// First code available for user defined models.
+pub const USER_CODE_START: u32 = 0x100;
+
+/// Name reported for codes below `USER_CODE_START` without a built-in model.
+#[cfg(feature = "std")]
+const RESERVED_NAME: &str = "reserved";
+
+const BUILTIN: &[(u32, &str)] = &[
+    (VALUE, "value"),
+    (VEC, "vec"),
+    (MAP, "map"),
+    (DOUBLE_KEY_MAP, "double_key_map"),
+    (SET, "set"),
+    (DEQUE, "deque"),
+    (COUNTER, "counter"),
+];
+
+#[cfg(feature = "std")]
+static REGISTERED: std::sync::RwLock<alloc::collections::BTreeMap<u32, &'static str>> =
+    std::sync::RwLock::new(alloc::collections::BTreeMap::new());
+
+/// Name of a type code, `None` if it is neither built-in nor registered.
+pub fn type_name(code: u32) -> Option<&'static str> {
+    if code < USER_CODE_START {
+        return BUILTIN
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, name)| *name);
+    }
+
+    #[cfg(feature = "std")]
+    if let Ok(registered) = REGISTERED.read() {
+        return registered.get(&code).copied();
+    }
+
+    None
+}
+
+/// Register a type code for a user defined model.
+///
+/// Registering the same code with the same name again is a no-op. Names of built-in
+/// models and names registered with another code are rejected.
+#[cfg(feature = "std")]
+pub fn register(code: u32, name: &'static str) -> Result<()> {
+    if code < USER_CODE_START {
+        return Err(Error::TypeCodeConflict {
+            code,
+            name: type_name(code).unwrap_or(RESERVED_NAME),
+        });
+    }
+
+    let mut registered = REGISTERED.write().map_err(|_| Error::LockWriteError)?;
+    match registered.get(&code) {
+        Some(exist) if *exist == name => Ok(()),
+        Some(exist) => Err(Error::TypeCodeConflict { code, name: exist }),
+        None => {
+            let used = BUILTIN
+                .iter()
+                .copied()
+                .chain(registered.iter().map(|(c, n)| (*c, *n)))
+                .find(|(_, n)| *n == name);
+            if let Some((used, _)) = used {
+                return Err(Error::TypeNameConflict { name, code: used });
+            }
+            registered.insert(code, name);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//!
+//! set cache layer
+
+use core::{fmt::Debug, mem};
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Operation, OperationBytes, Result};
+
+use super::{Changes, Model};
+
+///
+/// define cache set
+/// use BTree
+///     key:K
+///     value:Operation<()>
+#[derive(Debug, Clone)]
+pub struct Set<K>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+{
+    pub(crate) value: BTreeMap<K, Operation<()>>,
+}
+
+impl<K> Default for Set<K>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+{
+    fn default() -> Self {
+        Self {
+            value: BTreeMap::new(),
+        }
+    }
+}
+
+///
+/// impl Model
+impl<K> Model for Set<K>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+{
+    ///define type 5
+    fn type_code(&self) -> u32 {
+        super::registry::SET
+    }
+
+    /// Consume the data in the cache
+    /// Also convert key to vec<u8>, members are stored as empty value
+    #[cfg(feature = "cbor")]
+    fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        use crate::OrderedKey;
+
+        let mut map = Vec::new();
+
+        let value = mem::take(&mut self.value);
+
+        for (k, v) in value.into_iter() {
+            let key = k.to_key_bytes()?;
+            let value = match v {
+                Operation::Update(()) => OperationBytes::Update(Vec::new()),
+                Operation::Delete => OperationBytes::Delete,
+            };
+            map.push((key, value));
+        }
+
+        Ok(map)
+    }
+
+    fn ordered_keys(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "cbor")]
+    fn upgrade_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        super::upgrade_cbor_key::<K>(key)
+    }
+
+    /// Merge two caches
+    fn merge(&mut self, other: Self) {
+        let mut value = other.value;
+        self.value.append(&mut value);
+    }
+}
+
+impl<K> Changes for Set<K>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+{
+    type Key = K;
+    type Value = ();
+
+    /// Members are stored as empty value.
+    fn decode(key: &[u8], operation: &OperationBytes) -> Result<Option<(K, Operation<()>)>> {
+        use crate::OrderedKey;
+
+        let k = K::from_key_bytes(key).map_err(|e| e.with_key(key))?;
+        let operation = match operation {
+            OperationBytes::Update(_) => Operation::Update(()),
+            OperationBytes::Delete => Operation::Delete,
+        };
+        Ok(Some((k, operation)))
+    }
+}
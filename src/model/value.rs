@@ -1,7 +1,7 @@
 //!
 //! value cache layer
 
-use core::{fmt::Debug, mem};
+use core::fmt::Debug;
 
 use alloc::vec::Vec;
 // use serde::{Deserialize, Serialize};
@@ -11,7 +11,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{Operation, OperationBytes, Result};
 
-use super::Model;
+use super::{Changes, Model};
 
 /// define value
 #[derive(Debug, Clone)]
@@ -50,7 +50,7 @@ where
 {
     /// define type 1
     fn type_code(&self) -> u32 {
-        1
+        super::registry::VALUE
     }
 
     /// Consume the data in the cache
@@ -58,7 +58,7 @@ where
     fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
         let mut vec = Vec::new();
 
-        let value = mem::replace(&mut self.value, None);
+        let value = self.value.take();
 
         if let Some(value) = value {
             // Empty key.
@@ -76,6 +76,18 @@ where
     }
 }
 
+impl<T> Changes for Value<T>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+{
+    type Key = ();
+    type Value = T;
+
+    fn decode(_key: &[u8], operation: &OperationBytes) -> Result<Option<((), Operation<T>)>> {
+        Ok(Some(((), Operation::from_bytes(operation)?)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::string::String;
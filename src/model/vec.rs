@@ -3,7 +3,7 @@
 //!
 use core::{fmt::Debug, mem};
 
-use crate::model::Model;
+use crate::model::{Changes, Model};
 use crate::{Operation, OperationBytes};
 use alloc::{collections::BTreeMap, vec::Vec as alloc_vec};
 #[cfg(feature = "cbor")]
@@ -38,14 +38,14 @@ where
     /// Consume the data in the cache
     /// Also convert key to vec<u8>
     fn operations(&mut self) -> crate::Result<alloc_vec<(alloc_vec<u8>, OperationBytes)>> {
-        use crate::utils::cbor_encode;
+        use crate::OrderedKey;
 
         let mut map = alloc_vec::new();
 
         let value = mem::take(&mut self.value);
 
         for (k, v) in value.into_iter() {
-            let key = cbor_encode(k)?;
+            let key = k.to_key_bytes()?;
             let value = v.to_bytes()?;
             map.push((key, value));
         }
@@ -55,7 +55,15 @@ where
 
     /// define type 2
     fn type_code(&self) -> u32 {
-        2
+        super::registry::VEC
+    }
+
+    fn ordered_keys(&self) -> bool {
+        true
+    }
+
+    fn upgrade_key(&self, key: &[u8]) -> crate::Result<alloc_vec<u8>> {
+        super::upgrade_cbor_key::<u64>(key)
     }
 
     /// Merge two caches
@@ -64,3 +72,21 @@ where
         self.value.append(&mut value);
     }
 }
+
+impl<V> Changes for Vec<V>
+where
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    type Key = u64;
+    type Value = V;
+
+    fn decode(
+        key: &[u8],
+        operation: &OperationBytes,
+    ) -> crate::Result<Option<(u64, Operation<V>)>> {
+        use crate::OrderedKey;
+
+        let index = u64::from_key_bytes(key).map_err(|e| e.with_key(key))?;
+        Ok(Some((index, Operation::from_bytes(operation)?)))
+    }
+}
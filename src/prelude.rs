@@ -1,6 +1,9 @@
+#[cfg(feature = "json")]
 use crate::Result;
+#[cfg(feature = "json")]
 use alloc::vec::Vec;
 
+#[cfg(feature = "json")]
 pub trait Tree {
     /// Get value by key in tree.
     fn tree_get(&self, key: &[u8]) -> Result<Vec<u8>>;
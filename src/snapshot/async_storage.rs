@@ -0,0 +1,228 @@
+//!
+//! Async storage facade
+//!
+//! `AsyncStorage` runs a `SnapshotableStorage` on the blocking pool of tokio, so
+//! commits and reads of a sync backend don't block the executor. Reads share the
+//! storage, writes and commits take it exclusively.
+//!
+//! A storage `open`ed on an `AsyncStore` waits on it from the blocking pool through
+//! `AsyncBackend`.
+
+use core::fmt::Debug;
+
+use alloc::{string::String, sync::Arc};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{async_store::blocking, AsyncBackend, AsyncStore},
+    merkle::Merkle,
+    model::{Counter, CounterValue, DoubleKeyMap, Map, Model, Set, Value, Vec},
+    CounterStore, DoubleKeyMapStore, MapStore, Result, SetStore, Store, ValueStore, VecStore,
+};
+
+use super::{CommitMetadata, SnapshotableStorage};
+
+/// Storage shared by async tasks, clones share the storage.
+pub struct AsyncStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    inner: Arc<RwLock<SnapshotableStorage<S, M, V>>>,
+}
+
+impl<S, M, V> Clone for AsyncStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S, M, V> AsyncStorage<S, M, V>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    V: Model + Send + Sync + 'static,
+{
+    pub fn new(storage: SnapshotableStorage<S, M, V>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(storage)),
+        }
+    }
+
+    /// Run `f` on the storage.
+    pub async fn read<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&SnapshotableStorage<S, M, V>) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        blocking(move || f(&inner.read().unwrap_or_else(|e| e.into_inner()))).await
+    }
+
+    /// Run `f` on the storage exclusively.
+    pub async fn write<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut SnapshotableStorage<S, M, V>) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        blocking(move || f(&mut inner.write().unwrap_or_else(|e| e.into_inner()))).await
+    }
+
+    pub async fn height(&self) -> Result<i64> {
+        self.read(|s| Ok(s.height)).await
+    }
+
+    /// Commit this snapshot.
+    pub async fn commit(&self) -> Result<i64> {
+        self.write(|s| s.commit()).await
+    }
+
+    /// Commit this snapshot with metadata.
+    pub async fn commit_with_metadata(&self, metadata: CommitMetadata) -> Result<i64> {
+        self.write(move |s| s.commit_with_metadata(metadata)).await
+    }
+
+    /// Rollback to point height, target_height must less than current height.
+    pub async fn rollback(&self, target_height: i64) -> Result<()> {
+        self.write(move |s| s.rollback(target_height)).await
+    }
+}
+
+impl<A, M, V> AsyncStorage<AsyncBackend<A>, M, V>
+where
+    A: AsyncStore + 'static,
+    M: Merkle + Send + Sync + 'static,
+    V: Model + Send + Sync + 'static,
+{
+    /// Load storage `name` from an async store, or create it.
+    pub async fn open(value: V, name: String, store: A) -> Result<Self> {
+        let storage = blocking(move || {
+            SnapshotableStorage::new_with_name(value, name, AsyncBackend::new(store))
+        })
+        .await?;
+        Ok(Self::new(storage))
+    }
+}
+
+impl<S, M, K, V> AsyncStorage<S, M, Map<K, V>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    K: Clone
+        + PartialEq
+        + Eq
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + Ord
+        + PartialOrd
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + 'static,
+{
+    pub async fn get(&self, key: K) -> Result<Option<V>> {
+        self.read(move |s| Ok(s.get(&key)?.map(|v| v.clone())))
+            .await
+    }
+}
+
+impl<S, M, T> AsyncStorage<S, M, Value<T>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static,
+{
+    pub async fn get(&self) -> Result<Option<T>> {
+        self.read(|s| Ok(s.get()?.map(|v| v.clone()))).await
+    }
+}
+
+impl<S, M, T> AsyncStorage<S, M, Vec<T>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static,
+{
+    pub async fn get(&self, index: u64) -> Result<Option<T>> {
+        self.read(move |s| Ok(s.get(index)?.map(|v| v.clone())))
+            .await
+    }
+}
+
+impl<S, M, K1, K2, V> AsyncStorage<S, M, DoubleKeyMap<K1, K2, V>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    K1: Clone
+        + PartialEq
+        + Eq
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + Ord
+        + PartialOrd
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+    K2: Clone
+        + PartialEq
+        + Eq
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + Ord
+        + PartialOrd
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug + Send + Sync + 'static,
+{
+    pub async fn get(&self, key1: K1, key2: K2) -> Result<Option<V>> {
+        self.read(move |s| Ok(s.get(&key1, &key2)?.map(|v| v.clone())))
+            .await
+    }
+}
+
+impl<S, M, K> AsyncStorage<S, M, Set<K>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    K: Clone
+        + PartialEq
+        + Eq
+        + Serialize
+        + for<'de> Deserialize<'de>
+        + Ord
+        + PartialOrd
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+{
+    pub async fn contains(&self, key: K) -> Result<bool> {
+        self.read(move |s| s.contains(&key)).await
+    }
+}
+
+impl<S, M, N> AsyncStorage<S, M, Counter<N>>
+where
+    S: Store + 'static,
+    M: Merkle + Send + Sync + 'static,
+    N: CounterValue + Send + Sync + 'static,
+{
+    pub async fn get(&self) -> Result<N> {
+        self.read(|s| s.get()).await
+    }
+}
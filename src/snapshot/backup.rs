@@ -0,0 +1,214 @@
+//!
+//! Online backup
+//!
+//! A backup holds all versions of a namespace up to a committed height, so it can
+//! be loaded, read at old heights and rolled back like the source.
+//!
+//! Commits only write versions above the current height, so a backup can be taken
+//! from a view sharing the backend (see `at`) while the source keeps committing.
+//! Rollback of the source below the backup height breaks the image. Clones of
+//! `MemoryBackend` don't share their records, a view of it is a deep copy.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, Error, Result};
+
+use super::{
+    fork::rebase_key, utils, FromStoreBytes, SnapshotableStorage, StoreHeight, ToStoreBytes,
+};
+
+/// Records written to destination per batch.
+const BACKUP_BATCH_SIZE: usize = 4096;
+
+/// Suffixes of the single records of a namespace in a backup.
+const BACKUP_KEYS: [&str; 2] = ["-ty", "-ch"];
+
+/// Suffixes of the ranges of records of a namespace in a backup: versions, latest
+/// index, merkle and commit records, expire schedule and secondary indexes.
+const BACKUP_RANGES: [&str; 6] = ["-kw-", "-lw-", "-mr-", "-cm-", "-ex-kw-", "-ix-"];
+
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Copy all versions up to a committed height into `dest`.
+    pub fn backup_to<B: Store>(&self, dest: &mut B, height: i64) -> Result<()> {
+        self.copy_versions(dest, 0, height)
+    }
+
+    /// Copy versions above `from_height` up to a committed height into `dest`,
+    /// which must hold a backup at `from_height`.
+    pub fn backup_incremental_to<B: Store>(
+        &self,
+        dest: &mut B,
+        from_height: i64,
+        height: i64,
+    ) -> Result<()> {
+        let key = utils::current_height_key(&self.namespace);
+        let bytes = dest
+            .get(&key)?
+            .ok_or_else(|| Error::KeyNotFound { key: key.clone() })?;
+        let found = StoreHeight::from_bytes(&bytes)
+            .map_err(|e| e.with_key(&key))?
+            .height;
+        if found != from_height {
+            return Err(Error::BackupHeightMismatch {
+                expected: from_height,
+                found,
+            });
+        }
+
+        self.copy_versions(dest, from_height, height)
+    }
+
+    /// Copy the backup of namespace `name` into an empty `store`, then load it and
+    /// check its root.
+    pub fn restore<B: Store>(
+        backup: &B,
+        value: V,
+        name: String,
+        mut store: S,
+        root: &[u8],
+    ) -> Result<Self> {
+        restore_backup(backup, &mut store, &name)?;
+
+        let restored = Self::new_with_name(value, name, store)?;
+        if restored.root()?[..] != root[..] {
+            return Err(Error::BackupRootMismatch {
+                height: restored.height,
+            });
+        }
+        Ok(restored)
+    }
+
+    /// Copy versions in `(from_height, height]`, current height is written last.
+    fn copy_versions<B: Store>(&self, dest: &mut B, from_height: i64, height: i64) -> Result<()> {
+        if height < from_height || height > self.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: self.height,
+            });
+        }
+
+        let mut batch = Vec::new();
+        let type_key = utils::type_key(&self.namespace);
+        if let Some(bytes) = self.store.get(&type_key)? {
+            batch.push((type_key, bytes.to_vec()));
+        }
+
+        // Versions of fork ancestors are copied into the namespace of the fork, later
+        // versions override earlier ones in `dest`.
+        for namespace in self.versioned_namespaces() {
+            let is_data = namespace == self.namespace;
+
+            for (branch, visible) in self.lineage(&namespace, height) {
+                let (begin_key, end_key) = utils::storage_key_range(&branch, []);
+                let mut latest: Option<(Vec<u8>, i64)> = None;
+
+                for (store_key, bytes) in self.store.range(&begin_key, &end_key)? {
+                    let (key, version) =
+                        utils::parse_storage_key(&branch, &store_key).ok_or_else(|| {
+                            Error::Corrupted {
+                                key: store_key.to_vec(),
+                                reason: "invalid storage key".to_string(),
+                            }
+                        })?;
+                    if version <= from_height || version > visible {
+                        continue;
+                    }
+                    batch.push((rebase_key(&store_key, &branch, &namespace), bytes.to_vec()));
+
+                    // Versions of one key are in ascending order.
+                    if is_data && self.latest_index {
+                        if let Some((last, version)) = latest.take() {
+                            if last != key {
+                                batch.push(latest_record(&self.namespace, &last, version)?);
+                            }
+                        }
+                        latest = Some((key, version));
+                    }
+
+                    if batch.len() >= BACKUP_BATCH_SIZE {
+                        dest.execute(core::mem::take(&mut batch))?;
+                    }
+                }
+                if let Some((last, version)) = latest {
+                    batch.push(latest_record(&self.namespace, &last, version)?);
+                }
+            }
+        }
+
+        // Each height is recorded by the oldest branch forked at or above it.
+        let mut lowest = from_height + 1;
+        for (branch, visible) in self.lineage(&self.namespace, height) {
+            for record_key in [utils::merkle_key, utils::commit_key] {
+                let begin_key = record_key(&branch, lowest);
+                let end_key = record_key(&branch, visible);
+                if begin_key <= end_key {
+                    for (key, bytes) in self.store.range(&begin_key, &end_key)? {
+                        batch.push((rebase_key(&key, &branch, &self.namespace), bytes.to_vec()));
+                    }
+                }
+            }
+            lowest = lowest.max(visible + 1);
+        }
+
+        let store_height = StoreHeight { height };
+        batch.push((
+            utils::current_height_key(&self.namespace),
+            store_height.to_bytes()?,
+        ));
+        dest.execute(batch)
+    }
+}
+
+fn latest_record(namespace: &str, key: &[u8], height: i64) -> Result<(Vec<u8>, Vec<u8>)> {
+    let store_height = StoreHeight { height };
+    Ok((utils::latest_key(namespace, key), store_height.to_bytes()?))
+}
+
+/// Copy all records of a namespace from `backup` into `store` without verifying
+/// its root, the namespace must be empty in `store`.
+///
+/// Namespaces starting with `{namespace}-` are not copied.
+pub fn restore_backup<B: Store, S: Store>(
+    backup: &B,
+    store: &mut S,
+    namespace: &str,
+) -> Result<()> {
+    let type_key = utils::type_key(namespace);
+    if backup.get(&type_key)?.is_none() {
+        return Err(Error::KeyNotFound { key: type_key });
+    }
+    if store.get(&type_key)?.is_some() {
+        return Err(Error::StoreNotEmpty {
+            namespace: namespace.to_string(),
+        });
+    }
+
+    let mut batch = Vec::new();
+    for suffix in BACKUP_KEYS {
+        let key = [namespace, suffix].concat().into_bytes();
+        if let Some(bytes) = backup.get(&key)? {
+            batch.push((key, bytes.to_vec()));
+        }
+    }
+
+    for suffix in BACKUP_RANGES {
+        let begin_key = [namespace, suffix].concat().into_bytes();
+        let mut end_key = begin_key.clone();
+        end_key.push(u8::MAX);
+        for (key, bytes) in backup.range(&begin_key, &end_key)? {
+            batch.push((key.to_vec(), bytes.to_vec()));
+            if batch.len() >= BACKUP_BATCH_SIZE {
+                store.execute(core::mem::take(&mut batch))?;
+            }
+        }
+    }
+    store.execute(batch)
+}
@@ -0,0 +1,99 @@
+//!
+//! Expire schedule of commit
+//!
+//! Keys inserted with ttl are scheduled under `{namespace}-ex` as `(expire_height, key)`,
+//! valued by the height the ttl was set. Commit of height `h` deletes keys scheduled at
+//! `h - 1`, unless they were written again after the ttl was set.
+//!
+//! Schedule and deletions are versioned like other records, so rollback brings back
+//! expired keys and the next commit expires them again.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+
+use crate::{
+    backend::Store, merkle::Merkle, model::Model, Operation, OperationBytes, OrderedKey, Result,
+    SnapshotableStorage,
+};
+
+use super::{utils, FromStoreBytes, StoreValue, ToStoreBytes};
+
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Schedule records of keys expiring at `expire_height`, set at `height`.
+    pub(crate) fn schedule_operations(
+        &self,
+        expirations: Vec<(Vec<u8>, i64)>,
+        height: i64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let namespace = utils::expire_namespace(&self.namespace);
+        let mut operations = Vec::new();
+
+        for (key, expire_height) in expirations {
+            let mut entry = expire_height.to_key_bytes()?;
+            entry.extend_from_slice(&key);
+            let store_value = StoreValue {
+                operation: Operation::Update(height).to_bytes()?,
+            };
+            operations.push((
+                utils::storage_key(&namespace, &entry, height),
+                store_value.to_bytes()?,
+            ));
+        }
+
+        Ok(operations)
+    }
+
+    /// Deletions of keys expiring before `height`, skipping keys written in `batch`.
+    pub(crate) fn expire_operations(
+        &self,
+        height: i64,
+        batch: &[(Vec<u8>, OperationBytes)],
+    ) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        let namespace = utils::expire_namespace(&self.namespace);
+        let prefix = (height - 1).to_key_bytes()?;
+        let (begin_key, end_key) = utils::storage_key_range(&namespace, &prefix);
+
+        let mut operations = Vec::new();
+        let scheduled = self.scan_range_operations(&namespace, &begin_key, &end_key)?;
+        if scheduled.is_empty() {
+            return Ok(operations);
+        }
+
+        let written: BTreeSet<&[u8]> = batch.iter().map(|(k, _)| k.as_slice()).collect();
+        for (entry, operation) in scheduled {
+            let set_height =
+                match Operation::<i64>::from_bytes(&operation).map_err(|e| e.with_key(&entry))? {
+                    Operation::Update(set_height) => set_height,
+                    Operation::Delete => continue,
+                };
+
+            let key = &entry[prefix.len()..];
+            if written.contains(key) {
+                continue;
+            }
+            // Writes after the ttl was set cancel it.
+            if let Some((version, Operation::Update(_))) = self.get_latest_version(key)? {
+                if version == set_height {
+                    operations.push((key.to_vec(), Operation::Delete));
+                }
+            }
+        }
+
+        Ok(operations)
+    }
+
+    /// Get the latest operation of key and its height, visible at current height.
+    fn get_latest_version(&self, key: &[u8]) -> Result<Option<(i64, OperationBytes)>> {
+        if let Some((store_key, bytes)) = self.get_version(key, self.height)? {
+            let value = StoreValue::from_bytes(&bytes).map_err(|e| e.with_key(&store_key))?;
+            if let Some(height) = utils::storage_key_height(&store_key) {
+                return Ok(Some((height, value.operation)));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,319 @@
+//!
+//! Named forks
+//!
+//! A fork branches the history of a store at height `H` into namespace
+//! `{namespace}@{name}.{generation}` and commits its own heights above `H`. It only
+//! holds the records it writes, reads of other keys fall back to its ancestors, each
+//! limited to the height it was forked at. Ancestors are recorded under `{fork}-br`,
+//! so a fork loads by its namespace like any store.
+//!
+//! Forks are registered in their parent under `{namespace}-fk-{name}`. Dropping only
+//! marks the entry and a fork created again with the same name gets a new generation.
+//! The parent can't roll back below a live fork, drop the fork first. Promotion writes `{namespace}-hd`, stores loaded by
+//! that namespace open the fork from then on.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "cbor")]
+use ciborium::de::from_reader;
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, utils::cbor_encode, Error, Result};
+
+use super::{utils, FromStoreBytes, SnapshotableStorage, StoreHeight, ToStoreBytes};
+
+/// Namespace read by a fork, up to the height the fork was created at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Branch {
+    pub namespace: String,
+    pub height: i64,
+}
+
+/// Ancestors of a fork, oldest first.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Lineage {
+    pub branches: Vec<Branch>,
+}
+
+/// Registry entry of a fork.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ForkRecord {
+    pub height: i64,
+    pub generation: u64,
+    pub dropped: bool,
+}
+
+macro_rules! impl_store_bytes {
+    ($($ty:ty),*) => {
+        $(
+            #[cfg(feature = "cbor")]
+            impl ToStoreBytes for $ty {
+                fn to_bytes(&self) -> Result<Vec<u8>> {
+                    let bytes = cbor_encode(self)?;
+                    Ok(bytes)
+                }
+            }
+
+            #[cfg(feature = "cbor")]
+            impl FromStoreBytes for $ty {
+                fn from_bytes(bytes: &[u8]) -> Result<Self> {
+                    let r = from_reader(bytes).map_err(|e| Error::CborDeIoError(e.to_string()))?;
+                    Ok(r)
+                }
+            }
+        )*
+    };
+}
+
+impl_store_bytes!(Branch, Lineage, ForkRecord);
+
+/// Follow promoted forks from namespace to the one loaded for it.
+pub(crate) fn resolve_head<S: Store>(store: &S, mut namespace: String) -> Result<String> {
+    // Forks are promoted over their parent only, so heads never cycle.
+    loop {
+        let key = utils::head_key(&namespace);
+        match store.get(&key)? {
+            Some(bytes) => {
+                namespace = Branch::from_bytes(&bytes)
+                    .map_err(|e| e.with_key(&key))?
+                    .namespace;
+            }
+            None => return Ok(namespace),
+        }
+    }
+}
+
+/// Methods for forks.
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Branch the history at a committed height into fork `name`.
+    ///
+    /// The returned store commits above `height` without touching this one.
+    pub fn fork(&mut self, name: &str, height: i64) -> Result<Self> {
+        self.check_writable()?;
+        if height < 0 || height > self.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: self.height,
+            });
+        }
+
+        let generation = match self.read_fork(name)? {
+            Some(record) if !record.dropped => {
+                return Err(Error::ForkExists {
+                    name: name.to_string(),
+                })
+            }
+            Some(record) => record.generation + 1,
+            None => 0,
+        };
+        let namespace = utils::fork_namespace(&self.namespace, name, generation);
+
+        // Ancestors forked above `height` hold versions the fork must not see.
+        let mut branches: Vec<Branch> = self
+            .branches
+            .iter()
+            .map(|branch| Branch {
+                namespace: branch.namespace.clone(),
+                height: branch.height.min(height),
+            })
+            .collect();
+        branches.push(Branch {
+            namespace: self.namespace.clone(),
+            height,
+        });
+
+        let mut batch = Vec::new();
+        if let Some(bytes) = self.store.get(&utils::type_key(&self.namespace))? {
+            batch.push((utils::type_key(&namespace), bytes.to_vec()));
+        }
+        // The fork's first commit chains its merkle root from `height`.
+        let owner = self.record_namespace(height);
+        for record_key in [utils::merkle_key, utils::commit_key] {
+            if let Some(bytes) = self.store.get(&record_key(owner, height))? {
+                batch.push((record_key(&namespace, height), bytes.to_vec()));
+            }
+        }
+        batch.push((
+            utils::branches_key(&namespace),
+            Lineage { branches }.to_bytes()?,
+        ));
+        batch.push((
+            utils::current_height_key(&namespace),
+            StoreHeight { height }.to_bytes()?,
+        ));
+        let record = ForkRecord {
+            height,
+            generation,
+            dropped: false,
+        };
+        batch.push((utils::fork_key(&self.namespace, name), record.to_bytes()?));
+        self.store.execute(batch)?;
+
+        self.open_fork(name)
+    }
+
+    /// Load fork `name` of this store.
+    pub fn open_fork(&self, name: &str) -> Result<Self> {
+        let record = match self.read_fork(name)? {
+            Some(record) if !record.dropped => record,
+            _ => {
+                return Err(Error::ForkNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let namespace = utils::fork_namespace(&self.namespace, name, record.generation);
+        let mut fork = Self::new_with_name(V::default(), namespace, self.store.clone())?;
+        fork.latest_index = self.latest_index;
+        fork.stats = self.stats;
+        fork.prune = self.prune;
+        fork.indexes = self.indexes.clone();
+        Ok(fork)
+    }
+
+    /// Read only view of fork `name` at a height committed by the fork.
+    pub fn at_fork(&self, name: &str, height: i64) -> Result<Self> {
+        self.open_fork(name)?.at(height)
+    }
+
+    /// Unregister fork `name`, its records are kept in the backend.
+    pub fn drop_fork(&mut self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        match self.read_fork(name)? {
+            Some(mut record) if !record.dropped => {
+                record.dropped = true;
+                self.store
+                    .insert(utils::fork_key(&self.namespace, name), record.to_bytes()?)
+            }
+            _ => Err(Error::ForkNotFound {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Make `fork`, opened from this store, the line of this namespace.
+    ///
+    /// This store switches to the fork and takes its backend, the cache is dropped.
+    /// Stores loaded by this namespace open the fork from now on.
+    pub fn promote(&mut self, fork: Self) -> Result<()> {
+        let parent = match fork.branches.last() {
+            Some(parent) if parent.namespace == self.namespace => parent.clone(),
+            _ => {
+                return Err(Error::ForkNotFound {
+                    name: fork.namespace,
+                })
+            }
+        };
+
+        let Self {
+            mut store,
+            height,
+            namespace,
+            merkle,
+            branches,
+            ..
+        } = fork;
+        let head = Branch {
+            namespace: namespace.clone(),
+            height: parent.height,
+        };
+        store.insert(utils::head_key(&self.namespace), head.to_bytes()?)?;
+
+        self.store = store;
+        self.height = height;
+        self.value = V::default();
+        self.namespace = namespace;
+        self.merkle = merkle;
+        self.branches = branches;
+        Ok(())
+    }
+
+    fn read_fork(&self, name: &str) -> Result<Option<ForkRecord>> {
+        let key = utils::fork_key(&self.namespace, name);
+        match self.store.get(&key)? {
+            Some(bytes) => Ok(Some(
+                ForkRecord::from_bytes(&bytes).map_err(|e| e.with_key(&key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Fail if a live fork of this store was created above height.
+    pub(crate) fn check_forks_below(&self, height: i64) -> Result<()> {
+        let begin_key = utils::fork_key(&self.namespace, "");
+        let mut end_key = begin_key.clone();
+        end_key.push(u8::MAX);
+
+        for (key, bytes) in self.store.range(&begin_key, &end_key)? {
+            let record = ForkRecord::from_bytes(&bytes).map_err(|e| e.with_key(&key))?;
+            if !record.dropped && record.height > height {
+                return Err(Error::ForkedAbove {
+                    name: String::from_utf8_lossy(&key[begin_key.len()..]).into_owned(),
+                    height: record.height,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ancestors recorded for the namespace of this store.
+    pub(crate) fn read_branches(&self) -> Result<Vec<Branch>> {
+        let key = utils::branches_key(&self.namespace);
+        match self.store.get(&key)? {
+            Some(bytes) => Ok(Lineage::from_bytes(&bytes)
+                .map_err(|e| e.with_key(&key))?
+                .branches),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Lowest height this store can be rolled back to.
+    pub(crate) fn base_height(&self) -> i64 {
+        self.branches.last().map(|b| b.height).unwrap_or(0)
+    }
+
+    /// Namespaces holding the versions of `namespace` visible at `height`, oldest first,
+    /// each with the highest height visible in it.
+    ///
+    /// `namespace` is the one of this store or a sub namespace of it.
+    pub(crate) fn lineage(&self, namespace: &str, height: i64) -> Vec<(String, i64)> {
+        let suffix = &namespace[self.namespace.len()..];
+        let mut namespaces: Vec<(String, i64)> = self
+            .branches
+            .iter()
+            .map(|branch| {
+                let mut namespace = branch.namespace.clone();
+                namespace.push_str(suffix);
+                (namespace, branch.height.min(height))
+            })
+            .collect();
+        namespaces.push((namespace.to_string(), height));
+        namespaces
+    }
+
+    /// Namespace holding the merkle and commit records of a height.
+    pub(crate) fn record_namespace(&self, height: i64) -> &str {
+        self.branches
+            .iter()
+            .find(|branch| branch.height >= height)
+            .map(|branch| branch.namespace.as_str())
+            .unwrap_or(&self.namespace)
+    }
+}
+
+/// Move a record key of `from` namespace to `to`.
+pub(crate) fn rebase_key(key: &[u8], from: &str, to: &str) -> Vec<u8> {
+    let mut rebased = to.as_bytes().to_vec();
+    rebased.extend_from_slice(&key[from.len()..]);
+    rebased
+}
@@ -0,0 +1,125 @@
+//!
+//! Commit hooks
+//!
+//! Validators run before a commit writes anything and can veto it, listeners run
+//! after a successful commit. Both see the operations of the commit, including
+//! deletions of expired keys, decoded on demand by `Changes`.
+
+use core::marker::PhantomData;
+
+use alloc::{sync::Arc, vec::Vec};
+use digest::Output;
+
+use crate::{
+    backend::Store,
+    merkle::Merkle,
+    model::{Change, Changes, Model},
+    OperationBytes, Result,
+};
+
+use super::{CommitMetadata, SnapshotableStorage};
+
+pub(crate) type CommitValidator<V> = Arc<dyn Fn(&PendingCommit<'_, V>) -> Result<()> + Send + Sync>;
+
+pub(crate) type CommitListener<V, D> = Arc<dyn Fn(&CommitEvent<'_, V, D>) + Send + Sync>;
+
+/// Operations about to be written at `height`.
+pub struct PendingCommit<'a, V> {
+    pub height: i64,
+    batch: &'a [(Vec<u8>, OperationBytes)],
+    marker: PhantomData<V>,
+}
+
+/// Operations written at `height`, with the new root.
+pub struct CommitEvent<'a, V, D: digest::Digest> {
+    pub height: i64,
+    pub root: &'a Output<D>,
+    pub metadata: Option<&'a CommitMetadata>,
+    batch: &'a [(Vec<u8>, OperationBytes)],
+    marker: PhantomData<V>,
+}
+
+impl<'a, V> PendingCommit<'a, V> {
+    pub(crate) fn new(height: i64, batch: &'a [(Vec<u8>, OperationBytes)]) -> Self {
+        Self {
+            height,
+            batch,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, V: Changes> PendingCommit<'a, V> {
+    pub fn operations(&self) -> Result<Vec<Change<V>>> {
+        decode::<V>(self.batch)
+    }
+}
+
+impl<'a, V, D: digest::Digest> CommitEvent<'a, V, D> {
+    pub(crate) fn new(
+        height: i64,
+        root: &'a Output<D>,
+        metadata: Option<&'a CommitMetadata>,
+        batch: &'a [(Vec<u8>, OperationBytes)],
+    ) -> Self {
+        Self {
+            height,
+            root,
+            metadata,
+            batch,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, V: Changes, D: digest::Digest> CommitEvent<'a, V, D> {
+    pub fn operations(&self) -> Result<Vec<Change<V>>> {
+        decode::<V>(self.batch)
+    }
+}
+
+pub(crate) fn decode<V: Changes>(batch: &[(Vec<u8>, OperationBytes)]) -> Result<Vec<Change<V>>> {
+    let mut operations = Vec::new();
+    for (key, operation) in batch {
+        if let Some(operation) = V::decode(key, operation)? {
+            operations.push(operation);
+        }
+    }
+    Ok(operations)
+}
+
+/// Commit hook methods
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Run `f` before each commit, an error vetoes the commit and keeps the cache.
+    pub fn with_commit_validator<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&PendingCommit<'_, V>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.validators.push(Arc::new(f));
+        self
+    }
+
+    /// Run `f` after each successful commit.
+    pub fn with_commit_listener<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&CommitEvent<'_, V, M::Digest>) + Send + Sync + 'static,
+    {
+        self.listeners.push(Arc::new(f));
+        self
+    }
+
+    /// Operations the next commit would write, the cache is kept.
+    pub fn pending_operations(&self) -> Result<Vec<Change<V>>>
+    where
+        V: Changes,
+    {
+        let mut value = self.value.clone();
+        let (batch, _) = self.take_batch(&mut value, self.height + 1)?;
+        decode::<V>(&batch)
+    }
+}
@@ -0,0 +1,21 @@
+//!
+//! Secondary index hook of commit
+//!
+
+use alloc::vec::Vec;
+
+use crate::{OperationBytes, Result};
+
+/// Index maintained in the commit batch of its primary store.
+pub(crate) trait IndexOperations: Send + Sync {
+    /// Name of this index, unique in primary store.
+    fn name(&self) -> &str;
+
+    /// Index operations for a primary key changing from `old` to `new`.
+    fn operations(
+        &self,
+        key: &[u8],
+        old: Option<&OperationBytes>,
+        new: &OperationBytes,
+    ) -> Result<Vec<(Vec<u8>, OperationBytes)>>;
+}
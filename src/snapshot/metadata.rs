@@ -0,0 +1,101 @@
+//!
+//! Commit metadata
+//!
+//! Each commit writes a record under `{namespace}-cm-{height}` with the number of
+//! keys it wrote and the metadata given to `commit_with_metadata`.
+
+use alloc::{string::ToString, vec::Vec};
+use ciborium::de::from_reader;
+use digest::Output;
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, utils::cbor_encode, Error, Result};
+
+use super::{utils, FromStoreBytes, SnapshotableStorage, ToStoreBytes};
+
+/// Metadata of a commit, given by the application.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitMetadata {
+    /// Block time, unix timestamp in milliseconds.
+    pub time: Option<i64>,
+    pub proposer: Option<Vec<u8>>,
+    pub app_version: Option<u64>,
+    /// Custom header.
+    pub extra: Vec<u8>,
+}
+
+/// Record written by each commit.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CommitRecord {
+    pub keys: u64,
+    pub metadata: Option<CommitMetadata>,
+    /// Keys written, in the order the merkle tree hashed them.
+    #[serde(default)]
+    pub order: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "cbor")]
+impl ToStoreBytes for CommitRecord {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let bytes = cbor_encode(self)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl FromStoreBytes for CommitRecord {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let r = from_reader(bytes).map_err(|e| Error::CborDeIoError(e.to_string()))?;
+        Ok(r)
+    }
+}
+
+/// What a height committed, see `commit_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo<D: digest::Digest> {
+    pub height: i64,
+    pub root: Output<D>,
+    /// Count of keys written, including deletions.
+    pub keys: u64,
+    pub metadata: Option<CommitMetadata>,
+}
+
+/// Commit metadata methods
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Commit this snapshot with metadata.
+    pub fn commit_with_metadata(&mut self, metadata: CommitMetadata) -> Result<i64> {
+        self.commit_inner(Some(metadata))
+    }
+
+    /// Root, count of keys written and metadata of a committed height.
+    ///
+    /// `None` if the height was committed without a commit record.
+    pub fn commit_info(&self, height: i64) -> Result<Option<CommitInfo<M::Digest>>> {
+        if height < 1 || height > self.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: self.height,
+            });
+        }
+
+        let key = utils::commit_key(self.record_namespace(height), height);
+        let record = match self.store.get(&key)? {
+            Some(bytes) => CommitRecord::from_bytes(&bytes).map_err(|e| e.with_key(&key))?,
+            None => return Ok(None),
+        };
+
+        Ok(Some(CommitInfo {
+            height,
+            root: self.at(height)?.root()?,
+            keys: record.keys,
+            metadata: record.metadata,
+        }))
+    }
+}
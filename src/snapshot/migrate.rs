@@ -0,0 +1,94 @@
+//!
+//! Key layout migration
+//!
+//! Namespaces of collection models written by bs3 0.1 hold cbor keys, key layout 0,
+//! and fail to load with `Error::LayoutMismatch`. `migrate_key_layout` copies such a
+//! namespace into another backend with its versions and latest index re-encoded by
+//! `Model::upgrade_key` at the heights they were committed. The source is not
+//! modified, an interrupted migration is run again on an empty target.
+//!
+//! Merkle records are copied as they are, roots of the migrated heights were computed
+//! over the cbor keys. Commits after the migration chain from them.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, Error, Result};
+
+use super::{
+    utils,
+    value::{StoreType, KEY_LAYOUT},
+    FromStoreBytes, SnapshotableStorage, ToStoreBytes,
+};
+
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Copy namespace `name` written with key layout 0 from `source` into `store` with
+    /// the current key layout, and load it.
+    ///
+    /// Fails with `Error::StoreNotEmpty` if `store` has the namespace, and with
+    /// `Error::LayoutMismatch` if the namespace isn't at layout 0.
+    pub fn migrate_key_layout(value: V, name: String, source: &S, mut store: S) -> Result<Self> {
+        let type_key = utils::type_key(&name);
+        let bytes = source.get(&type_key)?.ok_or_else(|| Error::KeyNotFound {
+            key: type_key.clone(),
+        })?;
+        let mut store_type = StoreType::from_bytes(&bytes).map_err(|e| e.with_key(&type_key))?;
+        if store_type.layout != 0 {
+            return Err(Error::LayoutMismatch {
+                namespace: name,
+                expected: 0,
+                found: store_type.layout,
+            });
+        }
+        if store.get(&type_key)?.is_some() {
+            return Err(Error::StoreNotEmpty { namespace: name });
+        }
+
+        let upgrade = |key: &[u8]| match value.ordered_keys() {
+            true => value.upgrade_key(key),
+            false => Ok(key.to_vec()),
+        };
+
+        let corrupted = |key: &[u8]| Error::Corrupted {
+            key: key.to_vec(),
+            reason: String::from("malformed key"),
+        };
+
+        let mut batch = Vec::new();
+        let (begin_key, end_key) = utils::storage_key_range(&name, b"");
+        for (key, bytes) in source.range(&begin_key, &end_key)? {
+            let (k, height) =
+                utils::parse_storage_key(&name, &key).ok_or_else(|| corrupted(&key))?;
+            batch.push((
+                utils::storage_key(&name, upgrade(&k)?, height),
+                bytes.to_vec(),
+            ));
+        }
+
+        let (begin_key, end_key) = utils::latest_key_range(&name);
+        for (key, bytes) in source.range(&begin_key, &end_key)? {
+            let k = hex::decode(&key[begin_key.len()..]).map_err(|_| corrupted(&key))?;
+            batch.push((utils::latest_key(&name, upgrade(&k)?), bytes.to_vec()));
+        }
+
+        let begin_key = utils::merkle_key(&name, 0);
+        let end_key = utils::merkle_key(&name, i64::MAX);
+        for (key, bytes) in source.range(&begin_key, &end_key)? {
+            batch.push((key.to_vec(), bytes.to_vec()));
+        }
+
+        let height_key = utils::current_height_key(&name);
+        if let Some(bytes) = source.get(&height_key)? {
+            batch.push((height_key, bytes.to_vec()));
+        }
+        store_type.layout = KEY_LAYOUT;
+        batch.push((type_key, store_type.to_bytes()?));
+        store.execute(batch)?;
+
+        Self::new_with_name(value, name, store)
+    }
+}
@@ -6,8 +6,49 @@ pub mod utils;
 mod value;
 pub use value::{FromStoreBytes, StoreHeight, StoreValue, ToStoreBytes};
 
+pub(crate) mod index;
+
+mod expire;
+
+mod fork;
+
+mod metadata;
+pub use metadata::{CommitInfo, CommitMetadata};
+
+mod hooks;
+pub use hooks::{CommitEvent, PendingCommit};
+
+mod backup;
+pub use backup::restore_backup;
+
+mod sync;
+pub use sync::{
+    import_snapshot, Snapshot, SnapshotChunks, SnapshotImporter, SnapshotManifest, SnapshotWriter,
+    SNAPSHOT_CHUNK_SIZE, SNAPSHOT_FORMAT,
+};
+
+mod stats;
+pub use stats::NamespaceStats;
+
+mod migrate;
+
+pub(crate) mod staged;
+
+mod verify;
+pub use verify::{verify, NamespaceReport, Problem, ProblemKind, VerifyReport};
+
 mod storage;
 pub use storage::SnapshotableStorage;
 
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+pub use shared::{SharedStorage, StorageReader};
+
+#[cfg(feature = "async")]
+mod async_storage;
+#[cfg(feature = "async")]
+pub use async_storage::AsyncStorage;
+
 mod transaction;
 pub use transaction::*;
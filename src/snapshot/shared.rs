@@ -0,0 +1,166 @@
+//!
+//! Concurrent readers
+//!
+//! A single writer commits while readers on other threads take snapshots pinned at
+//! committed heights. Each commit publishes a read only view (see `at`), readers
+//! only hold the lock to clone its pointer, so snapshots keep reading their height
+//! while the writer commits newer ones.
+//!
+//! Views share backends which clone by handle, like sled. A memory backend is copied
+//! for each published view.
+
+use core::ops::{Deref, DerefMut};
+
+use std::sync::{Arc, RwLock};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, Result};
+
+use super::{CommitMetadata, SnapshotableStorage};
+
+/// Writer of a store shared with `StorageReader`s.
+///
+/// Commit and rollback through this handle to publish them.
+pub struct SharedStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    storage: SnapshotableStorage<S, M, V>,
+    reader: StorageReader<S, M, V>,
+}
+
+/// Handle giving snapshots of the latest height published by a `SharedStorage`.
+pub struct StorageReader<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    latest: Arc<RwLock<Arc<SnapshotableStorage<S, M, V>>>>,
+}
+
+impl<S, M, V> Clone for StorageReader<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    fn clone(&self) -> Self {
+        Self {
+            latest: self.latest.clone(),
+        }
+    }
+}
+
+impl<S, M, V> SharedStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Share a store, its current height is published.
+    pub fn new(storage: SnapshotableStorage<S, M, V>) -> Result<Self> {
+        let view = storage.at(storage.height)?;
+        Ok(Self {
+            storage,
+            reader: StorageReader {
+                latest: Arc::new(RwLock::new(Arc::new(view))),
+            },
+        })
+    }
+
+    /// Handle for readers, it can be cloned and sent to other threads.
+    pub fn reader(&self) -> StorageReader<S, M, V> {
+        self.reader.clone()
+    }
+
+    /// Commit and publish the new height.
+    pub fn commit(&mut self) -> Result<i64> {
+        let height = self.storage.commit()?;
+        self.publish()?;
+        Ok(height)
+    }
+
+    /// Commit with metadata and publish the new height.
+    pub fn commit_with_metadata(&mut self, metadata: CommitMetadata) -> Result<i64> {
+        let height = self.storage.commit_with_metadata(metadata)?;
+        self.publish()?;
+        Ok(height)
+    }
+
+    /// Rollback and publish the target height.
+    ///
+    /// Snapshots pinned above the target height are stale, they read the versions
+    /// committed after it until the writer commits their heights again. Stores built
+    /// `with_pruning` remove those versions, the snapshots then read the target height.
+    pub fn rollback(&mut self, target_height: i64) -> Result<()> {
+        self.storage.rollback(target_height)?;
+        self.publish()
+    }
+
+    pub fn into_inner(self) -> SnapshotableStorage<S, M, V> {
+        self.storage
+    }
+
+    fn publish(&mut self) -> Result<()> {
+        let view = Arc::new(self.storage.at(self.storage.height)?);
+        // Readers never panic while holding the lock, poison is ignored.
+        let mut latest = self
+            .reader
+            .latest
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        *latest = view;
+        Ok(())
+    }
+}
+
+impl<S, M, V> Deref for SharedStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    type Target = SnapshotableStorage<S, M, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+impl<S, M, V> DerefMut for SharedStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.storage
+    }
+}
+
+impl<S, M, V> StorageReader<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Latest published height.
+    pub fn height(&self) -> i64 {
+        self.snapshot().height
+    }
+
+    /// Snapshot at the latest published height.
+    pub fn snapshot(&self) -> Arc<SnapshotableStorage<S, M, V>> {
+        self.latest
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Snapshot at a published height.
+    pub fn snapshot_at(&self, height: i64) -> Result<SnapshotableStorage<S, M, V>> {
+        self.snapshot().at(height)
+    }
+}
@@ -0,0 +1,313 @@
+//!
+//! Staged commit
+//!
+//! A commit is staged into the records it writes, including the merkle record, then
+//! applied with one `Store::execute`. Composite states stage every storage, write the
+//! records of all of them in one batch, and abort the staged and applied ones if one
+//! fails. Listeners are notified once every storage is applied.
+
+use core::mem;
+
+use alloc::vec::Vec;
+
+use digest::Output;
+
+use crate::{
+    backend::Store,
+    merkle::Merkle,
+    model::{Change, Changes, Model},
+    OperationBytes, Result,
+};
+
+use super::{
+    hooks::{self, CommitEvent, PendingCommit},
+    metadata::{CommitMetadata, CommitRecord},
+    stats::stats_operation,
+    utils, SnapshotableStorage, StoreHeight, StoreValue, ToStoreBytes,
+};
+
+/// Store collecting writes into a batch, reads go to the backend.
+#[derive(Clone)]
+pub(crate) struct StagingStore<'a, S> {
+    store: &'a S,
+    batch: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a, S: Store> Store for StagingStore<'a, S> {
+    type Range<'b>
+        = S::Range<'b>
+    where
+        Self: 'b;
+
+    fn range(&self, begin_key: &[u8], end_key: &[u8]) -> Result<Self::Range<'_>> {
+        self.store.range(begin_key, end_key)
+    }
+
+    fn execute(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        self.batch.extend(batch);
+        Ok(())
+    }
+}
+
+/// Commit staged by `SnapshotableStorage::stage_commit`.
+#[doc(hidden)]
+pub struct StagedCommit<V> {
+    height: i64,
+    operations: Vec<(Vec<u8>, Vec<u8>)>,
+    merkle_operations: Vec<(Vec<u8>, OperationBytes)>,
+    record: CommitRecord,
+    /// Keys written at the height of the commit, removed on abort by stores built
+    /// `with_pruning`.
+    written: Vec<Vec<u8>>,
+    /// Root after the commit, computed on apply for listeners.
+    root: Vec<u8>,
+    /// Cache before the commit, restored on abort.
+    saved: V,
+}
+
+impl<V> StagedCommit<V> {
+    /// Records written by the commit.
+    #[doc(hidden)]
+    pub fn operations(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.operations
+    }
+}
+
+impl<V: Changes> StagedCommit<V> {
+    /// Typed operations of the commit, including deletions of expired keys.
+    #[doc(hidden)]
+    pub fn changes(&self) -> Result<Vec<Change<V>>> {
+        hooks::decode::<V>(&self.merkle_operations)
+    }
+}
+
+/// Methods for staged commit
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Take the cache and build the records of the next commit without writing them.
+    ///
+    /// Validators run here. The cache is restored if staging fails.
+    #[doc(hidden)]
+    pub fn stage_commit(&mut self, metadata: Option<CommitMetadata>) -> Result<StagedCommit<V>> {
+        self.check_writable()?;
+        log::debug!("Snapshot Cache: {:?}", self.value);
+
+        let mut value = mem::take(&mut self.value);
+        let saved = value.clone();
+        match self.stage_operations(&mut value, metadata) {
+            Ok((operations, merkle_operations, record)) => Ok(StagedCommit {
+                height: self.height + 1,
+                operations,
+                merkle_operations,
+                record,
+                written: Vec::new(),
+                root: Vec::new(),
+                saved,
+            }),
+            Err(e) => {
+                self.value = saved;
+                Err(e)
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn stage_operations(
+        &mut self,
+        value: &mut V,
+        metadata: Option<CommitMetadata>,
+    ) -> Result<(
+        Vec<(Vec<u8>, Vec<u8>)>,
+        Vec<(Vec<u8>, OperationBytes)>,
+        CommitRecord,
+    )> {
+        let mut operations = Vec::new();
+        let mut merkle_operations = Vec::new();
+
+        // Values are written at the height this commit produces.
+        let height = self.height + 1;
+
+        let (batch, expirations) = self.take_batch(value, height)?;
+
+        let pending = PendingCommit::new(height, &batch);
+        for validator in self.validators.iter() {
+            validator(&pending)?;
+        }
+
+        operations.extend(self.schedule_operations(expirations, height)?);
+
+        let mut stats = match self.stats {
+            true => Some(self.base_stats()?),
+            false => None,
+        };
+
+        for (k, v) in batch {
+            if !self.indexes.is_empty() {
+                let old = self.get_latest_operation_bytes(&k)?;
+                for index in self.indexes.iter() {
+                    let namespace = utils::index_namespace(&self.namespace, index.name());
+                    for (index_key, operation) in index.operations(&k, old.as_ref(), &v)? {
+                        let store_value = StoreValue { operation };
+                        operations.push((
+                            utils::storage_key(&namespace, &index_key, height),
+                            store_value.to_bytes()?,
+                        ));
+                    }
+                }
+            }
+
+            let key_bytes = utils::storage_key(&self.namespace, &k, height);
+            let store_value = StoreValue {
+                operation: v.clone(),
+            };
+            let bytes = store_value.to_bytes()?;
+            if let Some(stats) = stats.as_mut() {
+                let old = self.own_operation_bytes(&k)?;
+                stats.record(&key_bytes, &bytes, old.as_ref(), &v, height);
+            }
+            operations.push((key_bytes, bytes));
+            if self.latest_index {
+                let store_height = StoreHeight { height };
+                let index_key = utils::latest_key(&self.namespace, &k);
+                operations.push((index_key, store_height.to_bytes()?));
+            }
+            merkle_operations.push((k, v));
+        }
+
+        let record = CommitRecord {
+            keys: merkle_operations.len() as u64,
+            metadata,
+            order: merkle_operations.iter().map(|(k, _)| k.clone()).collect(),
+        };
+        operations.push((
+            utils::commit_key(&self.namespace, height),
+            record.to_bytes()?,
+        ));
+
+        let store_height = StoreHeight { height };
+        operations.push((
+            utils::current_height_key(&self.namespace),
+            store_height.to_bytes()?,
+        ));
+
+        log::debug!("Start Compute merkle");
+        let mut staging = StagingStore {
+            store: &self.store,
+            batch: Vec::new(),
+        };
+        self.merkle.insert(&mut staging, &merkle_operations)?;
+
+        if let Some(stats) = stats {
+            operations.push(stats_operation(
+                stats,
+                &self.namespace,
+                height,
+                &staging.batch,
+            )?);
+        }
+        operations.extend(staging.batch);
+
+        Ok((operations, merkle_operations, record))
+    }
+
+    /// Write the records of a staged commit in one batch.
+    ///
+    /// Abort the commit if this fails, notify listeners by `notify_commit` once the
+    /// commit succeeded.
+    #[doc(hidden)]
+    pub fn apply_commit(&mut self, staged: &mut StagedCommit<V>) -> Result<i64> {
+        log::debug!("Begin sync snapshot success in height: {}", staged.height);
+
+        self.record_written(staged);
+        self.store.execute(mem::take(&mut staged.operations))?;
+        self.finish_commit(staged)
+    }
+
+    /// Apply a staged commit whose `operations` were written by `execute_batch` of a
+    /// storage over the same backend.
+    ///
+    /// Backends which don't share records across clones, like `MemoryBackend`, don't
+    /// hold the batch and the records are written here. Abort the commit if this fails.
+    #[doc(hidden)]
+    pub fn apply_shared_commit(&mut self, staged: &mut StagedCommit<V>) -> Result<i64> {
+        if self.read_height()? != staged.height {
+            return self.apply_commit(staged);
+        }
+        self.record_written(staged);
+        staged.operations = Vec::new();
+        self.finish_commit(staged)
+    }
+
+    /// Write the records of staged commits of storages over this backend in one batch.
+    #[doc(hidden)]
+    pub fn execute_batch(&mut self, batch: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        self.store.execute(batch)
+    }
+
+    fn record_written(&self, staged: &mut StagedCommit<V>) {
+        if self.prune {
+            let kept = [
+                utils::current_height_key(&self.namespace),
+                utils::latest_key_range(&self.namespace).0,
+            ];
+            staged.written = staged
+                .operations
+                .iter()
+                .map(|(k, _)| k.clone())
+                .filter(|k| !kept.iter().any(|prefix| k.starts_with(prefix)))
+                .collect();
+        }
+    }
+
+    fn finish_commit(&mut self, staged: &mut StagedCommit<V>) -> Result<i64> {
+        self.height = staged.height;
+
+        log::debug!("Sync snapshot success in height: {}", self.height);
+
+        if !self.listeners.is_empty() {
+            staged.root = self.root()?.to_vec();
+        }
+
+        Ok(self.height)
+    }
+
+    /// Run listeners of an applied commit.
+    #[doc(hidden)]
+    pub fn notify_commit(&self, staged: &StagedCommit<V>) {
+        if self.listeners.is_empty() {
+            return;
+        }
+        let mut root = Output::<M::Digest>::default();
+        root.copy_from_slice(&staged.root);
+        let event = CommitEvent::new(
+            staged.height,
+            &root,
+            staged.record.metadata.as_ref(),
+            &staged.merkle_operations,
+        );
+        for listener in self.listeners.iter() {
+            listener(&event);
+        }
+    }
+
+    /// Drop a staged commit and restore the cache, rolling back if it was applied.
+    ///
+    /// Only the keys written by the commit are removed, the namespace is not scanned.
+    #[doc(hidden)]
+    pub fn abort_commit(&mut self, staged: StagedCommit<V>) -> Result<()> {
+        self.value = staged.saved;
+        if self.height == staged.height {
+            self.rewind(staged.height - 1)?;
+            if self.prune {
+                self.store.remove(staged.written)?;
+            }
+            Ok(())
+        } else {
+            self.merkle.rollback(self.height)
+        }
+    }
+}
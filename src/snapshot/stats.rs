@@ -0,0 +1,200 @@
+//!
+//! Namespace statistics
+//!
+//! `stats` scans the versions and merkle records held by the namespace of a store.
+//! Stores built `with_stats` also write the statistics of each height under
+//! `{namespace}-st-{height}` in the batch of the commit, from the ones of the previous
+//! height and the batch, read them with `cached_stats`. The namespace is scanned
+//! again if the previous height was committed without them. Rollback of stores built
+//! `with_pruning` removes the ones above the target height with the versions.
+//!
+//! Versions held by the ancestors of a fork are not counted.
+
+use alloc::{string::ToString, vec::Vec};
+use ciborium::de::from_reader;
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::Store, merkle::Merkle, model::Model, utils::cbor_encode, Error, OperationBytes, Result,
+};
+
+use super::{utils, FromStoreBytes, SnapshotableStorage, StoreValue, ToStoreBytes};
+
+/// Statistics of the records of a namespace visible at a height.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceStats {
+    /// Keys whose latest version is an update.
+    pub live_keys: u64,
+    pub versions: u64,
+    /// Bytes of storage keys of all versions.
+    pub key_bytes: u64,
+    /// Bytes of records of all versions.
+    pub value_bytes: u64,
+    /// Versions which are `Operation::Delete`.
+    pub tombstones: u64,
+    pub oldest_height: Option<i64>,
+    pub newest_height: Option<i64>,
+    /// Bytes of keys and records of merkle roots.
+    pub merkle_bytes: u64,
+}
+
+#[cfg(feature = "cbor")]
+impl ToStoreBytes for NamespaceStats {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let bytes = cbor_encode(self)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl FromStoreBytes for NamespaceStats {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let r = from_reader(bytes).map_err(|e| Error::CborDeIoError(e.to_string()))?;
+        Ok(r)
+    }
+}
+
+impl NamespaceStats {
+    /// Count a version written at `height`, `old` is the operation it replaces.
+    pub(crate) fn record(
+        &mut self,
+        store_key: &[u8],
+        record: &[u8],
+        old: Option<&OperationBytes>,
+        operation: &OperationBytes,
+        height: i64,
+    ) {
+        self.versions += 1;
+        self.key_bytes += store_key.len() as u64;
+        self.value_bytes += record.len() as u64;
+
+        let was_live = matches!(old, Some(OperationBytes::Update(_)));
+        match operation {
+            OperationBytes::Update(_) if !was_live => self.live_keys += 1,
+            OperationBytes::Delete => {
+                self.tombstones += 1;
+                if was_live {
+                    self.live_keys -= 1;
+                }
+            }
+            _ => {}
+        }
+
+        self.oldest_height = Some(self.oldest_height.map_or(height, |h| h.min(height)));
+        self.newest_height = Some(self.newest_height.map_or(height, |h| h.max(height)));
+    }
+}
+
+/// Statistics methods
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Maintain statistics of the namespace on commit, see `cached_stats`.
+    ///
+    /// Each commit reads the previous version of the keys it writes.
+    pub fn with_stats(mut self) -> Self {
+        self.stats = true;
+        self
+    }
+
+    /// Scan statistics of the namespace at current height.
+    pub fn stats(&self) -> Result<NamespaceStats> {
+        let mut stats = NamespaceStats::default();
+
+        let (begin_key, end_key) = utils::storage_key_range(&self.namespace, []);
+        let mut last: Option<(Vec<u8>, OperationBytes)> = None;
+        for (store_key, bytes) in self.store.range(&begin_key, &end_key)? {
+            let (key, height) =
+                utils::parse_storage_key(&self.namespace, &store_key).ok_or_else(|| {
+                    Error::Corrupted {
+                        key: store_key.to_vec(),
+                        reason: "invalid storage key".to_string(),
+                    }
+                })?;
+            if height > self.height {
+                continue;
+            }
+            let value = StoreValue::from_bytes(&bytes).map_err(|e| e.with_key(&store_key))?;
+
+            // Versions of one key are in ascending order.
+            let old = match last.take() {
+                Some((last_key, operation)) if last_key == key => Some(operation),
+                _ => None,
+            };
+            stats.record(&store_key, &bytes, old.as_ref(), &value.operation, height);
+            last = Some((key, value.operation));
+        }
+
+        let begin_key = utils::merkle_key(&self.namespace, 0);
+        let end_key = utils::merkle_key(&self.namespace, self.height);
+        for (key, bytes) in self.store.range(&begin_key, &end_key)? {
+            stats.merkle_bytes += (key.len() + bytes.len()) as u64;
+        }
+
+        Ok(stats)
+    }
+
+    /// Statistics written by the commit of current height.
+    ///
+    /// `None` if that commit didn't maintain them, see `with_stats`.
+    pub fn cached_stats(&self) -> Result<Option<NamespaceStats>> {
+        let key = utils::stats_key(&self.namespace, self.height);
+        match self.store.get(&key)? {
+            Some(bytes) => Ok(Some(
+                NamespaceStats::from_bytes(&bytes).map_err(|e| e.with_key(&key))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Statistics the next commit starts from, scanned if the commit of current height
+    /// didn't write them.
+    pub(crate) fn base_stats(&self) -> Result<NamespaceStats> {
+        match self.cached_stats()? {
+            Some(stats) => Ok(stats),
+            None => self.stats(),
+        }
+    }
+
+    /// Keys of the statistics records above height, removed by rollback.
+    pub(crate) fn stats_keys_above(&self, height: i64) -> Result<Vec<Vec<u8>>> {
+        let begin_key = utils::stats_key(&self.namespace, height + 1);
+        let end_key = utils::stats_key(&self.namespace, i64::MAX);
+        let keys = self.store.range(&begin_key, &end_key)?;
+        Ok(keys.map(|(key, _)| key.to_vec()).collect())
+    }
+
+    /// Latest operation of key in the namespace of this store, ancestors are not read.
+    pub(crate) fn own_operation_bytes(&self, key: &[u8]) -> Result<Option<OperationBytes>> {
+        let begin_key = utils::storage_key(&self.namespace, key, 0);
+        let end_key = utils::storage_key(&self.namespace, key, self.height);
+        match self.store.range(&begin_key, &end_key)?.next_back() {
+            Some((store_key, bytes)) => Ok(Some(
+                StoreValue::from_bytes(&bytes)
+                    .map_err(|e| e.with_key(&store_key))?
+                    .operation,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Count the merkle record of `height` staged in `merkle_batch` and build the record of
+/// the statistics of `height`.
+pub(crate) fn stats_operation(
+    mut stats: NamespaceStats,
+    namespace: &str,
+    height: i64,
+    merkle_batch: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let key = utils::merkle_key(namespace, height);
+    if let Some((key, bytes)) = merkle_batch.iter().find(|(k, _)| *k == key) {
+        stats.merkle_bytes += (key.len() + bytes.len()) as u64;
+    }
+    Ok((utils::stats_key(namespace, height), stats.to_bytes()?))
+}
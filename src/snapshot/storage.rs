@@ -1,9 +1,34 @@
-use alloc::{string::String, vec::Vec};
-
-use crate::{backend::Store, merkle::Merkle, model::Model, snapshot::StoreValue, Error, Result};
-
-use super::{utils, value::StoreType, FromStoreBytes, StoreHeight, ToStoreBytes, Transaction};
-use crate::snapshot::utils::storage_key;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::Store,
+    merkle::Merkle,
+    model::{registry, Model},
+    snapshot::StoreValue,
+    CowBytes, Error, Operation, OperationBytes, Result,
+};
+
+use super::{
+    fork::{self, Branch},
+    hooks::{CommitListener, CommitValidator},
+    index::IndexOperations,
+    metadata::CommitMetadata,
+    utils,
+    value::{StoreType, KEY_LAYOUT},
+    FromStoreBytes, StoreHeight, ToStoreBytes, Transaction,
+};
+
+/// Suffixes of the ranges of versions of a namespace: its own, the expire schedule and
+/// secondary indexes.
+const VERSION_RANGES: [&str; 3] = ["-kw-", "-ex-kw-", "-ix-"];
 
 /// Snapshotable Storage
 #[derive(Clone)]
@@ -18,6 +43,14 @@ where
     pub(crate) value: V,
     pub(crate) namespace: String,
     pub(crate) merkle: M,
+    pub(crate) latest_index: bool,
+    pub(crate) stats: bool,
+    pub(crate) prune: bool,
+    pub(crate) view: bool,
+    pub(crate) indexes: Vec<Arc<dyn IndexOperations>>,
+    pub(crate) validators: Vec<CommitValidator<V>>,
+    pub(crate) listeners: Vec<CommitListener<V, M::Digest>>,
+    pub(crate) branches: Vec<Branch>,
 }
 
 /// Methods for create storage.
@@ -33,13 +66,24 @@ where
     }
 
     /// Create a `SnapshotableStorage` from store.
+    ///
+    /// If a fork was promoted over the namespace, the fork is loaded.
     pub fn new_with_name(value: V, name: String, store: S) -> Result<Self> {
+        let name = fork::resolve_head(&store, name)?;
         let mut s = Self {
             store,
             height: 0,
+            merkle: M::new(&name, 0),
             namespace: name,
             value,
-            merkle: M::default(),
+            latest_index: false,
+            stats: false,
+            prune: false,
+            view: false,
+            indexes: Vec::new(),
+            validators: Vec::new(),
+            listeners: Vec::new(),
+            branches: Vec::new(),
         };
 
         if !s.init_or_load()? {
@@ -51,6 +95,7 @@ where
                 s.height
             );
             s.height = s.read_height()?;
+            s.branches = s.read_branches()?;
             s.merkle.rollback(s.height)?;
         }
 
@@ -73,17 +118,27 @@ where
         namespace: String,
         store: S,
     ) -> Result<Self> {
+        let namespace = fork::resolve_head(&store, namespace)?;
         let mut s = Self {
             store,
             height,
             value,
+            merkle: M::new(&namespace, 0),
             namespace,
-            merkle: M::default(),
+            latest_index: false,
+            stats: false,
+            prune: false,
+            view: false,
+            indexes: Vec::new(),
+            validators: Vec::new(),
+            listeners: Vec::new(),
+            branches: Vec::new(),
         };
 
         if height == 0 {
             s.init()?;
         } else {
+            s.branches = s.read_branches()?;
             s.merkle.rollback(height)?;
             s.rollback(height)?;
         };
@@ -102,6 +157,7 @@ where
 
         let store_type = StoreType {
             ty: self.value.type_code(),
+            layout: KEY_LAYOUT,
         };
         let bytes = store_type.to_bytes()?;
         self.store.insert(key, bytes)?;
@@ -112,11 +168,26 @@ where
     /// Init or load a new store.
     fn init_or_load(&mut self) -> Result<bool> {
         let key = utils::type_key(&self.namespace);
-        if let Some(bytes) = self.store.get_ge(&key)? {
-            let store_type = StoreType::from_bytes(&bytes)?;
-            match store_type.ty == self.value.type_code() {
+        if let Some(bytes) = self.store.get(&key)? {
+            let store_type = StoreType::from_bytes(&bytes).map_err(|e| e.with_key(&key))?;
+            let expected = self.value.type_code();
+            if store_type.ty != expected {
+                return Err(Error::TypeMissMatch {
+                    expected,
+                    expected_name: self.value.type_name(),
+                    found: store_type.ty,
+                    found_name: registry::type_name(store_type.ty).unwrap_or("unknown"),
+                });
+            }
+            // Keys of other models are the same in every layout.
+            let upgraded = !self.value.ordered_keys() && store_type.layout < KEY_LAYOUT;
+            match store_type.layout == KEY_LAYOUT || upgraded {
                 true => Ok(false),
-                false => Err(Error::TypeMissMatch),
+                false => Err(Error::LayoutMismatch {
+                    namespace: self.namespace.clone(),
+                    expected: KEY_LAYOUT,
+                    found: store_type.layout,
+                }),
             }
         } else {
             self.init()?;
@@ -124,15 +195,91 @@ where
         }
     }
 
+    /// Maintain a latest version index for this store.
+    ///
+    /// The index records, for each key, the height of its latest committed version,
+    /// so reads of the current state become point lookups instead of range scans.
+    /// Keys missing from the index (e.g. written before it was enabled) fall back to
+    /// the range scan.
+    pub fn with_latest_index(mut self) -> Self {
+        self.latest_index = true;
+        self
+    }
+
+    /// Remove the versions and records above the target height on rollback.
+    ///
+    /// Without it they are kept in the backend and commits after a rollback write
+    /// over them, keys they don't write again read the rolled back versions. The
+    /// backend must implement `Store::remove`.
+    pub fn with_pruning(mut self) -> Self {
+        self.prune = true;
+        self
+    }
+
+    /// Read only view of this store at a committed height.
+    ///
+    /// The view shares the backend, commit, rollback and fork on it fail with
+    /// `Error::ReadOnlyView`.
+    pub fn at(&self, height: i64) -> Result<Self> {
+        if height < 0 || height > self.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: self.height,
+            });
+        }
+
+        let mut s = Self {
+            store: self.store.clone(),
+            height,
+            value: V::default(),
+            namespace: self.namespace.clone(),
+            // Roots below the fork height are recorded by ancestors.
+            merkle: M::new(self.record_namespace(height), 0),
+            latest_index: self.latest_index,
+            stats: self.stats,
+            prune: self.prune,
+            view: true,
+            indexes: self.indexes.clone(),
+            // Views don't commit, hooks are not copied.
+            validators: Vec::new(),
+            listeners: Vec::new(),
+            branches: self.branches.clone(),
+        };
+        s.merkle.rollback(height)?;
+
+        Ok(s)
+    }
+
+    /// Uncommitted cache, for stores of user defined models.
+    pub fn cache(&self) -> &V {
+        &self.value
+    }
+
+    pub fn cache_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Namespaces of versioned records: data, expire schedule and secondary indexes.
+    pub(crate) fn versioned_namespaces(&self) -> Vec<String> {
+        let mut namespaces = Vec::new();
+        namespaces.push(self.namespace.clone());
+        namespaces.push(utils::expire_namespace(&self.namespace));
+        for index in self.indexes.iter() {
+            namespaces.push(utils::index_namespace(&self.namespace, index.name()));
+        }
+        namespaces
+    }
+
     pub fn store(&self) -> &S {
         &self.store
     }
 
     pub fn get_with_height(&self, key: &str, height: i64) -> Result<Option<Vec<u8>>> {
-        let begin_key = storage_key(&*self.namespace, key, 0);
-        let end_key = storage_key(&*self.namespace, key, height);
-
-        if let Some(v) = self.store.get_ge2((&begin_key, &end_key))? {
+        if let Some((_, v)) = self.get_version(key.as_bytes(), height)? {
             Ok(Some(v.to_vec()))
         } else {
             Ok(None)
@@ -148,11 +295,11 @@ where
     V: Model,
 {
     /// Read current height in store.
-    fn read_height(&self) -> Result<i64> {
+    pub(crate) fn read_height(&self) -> Result<i64> {
         let key = utils::current_height_key(&self.namespace);
 
-        if let Some(bytes) = self.store.get_ge(&key)? {
-            let store_height = StoreHeight::from_bytes(&bytes)?;
+        if let Some(bytes) = self.store.get(&key)? {
+            let store_height = StoreHeight::from_bytes(&bytes).map_err(|e| e.with_key(&key))?;
             Ok(store_height.height)
         } else {
             Ok(0)
@@ -169,11 +316,7 @@ where
 
         self.height = target_height;
 
-        let mut operations = if let Some(ops) = pre_commit {
-            ops
-        } else {
-            Vec::new()
-        };
+        let mut operations = pre_commit.unwrap_or_default();
 
         let store_height = StoreHeight {
             height: self.height,
@@ -187,56 +330,276 @@ where
     }
 
     /// rollback to point height, target_height must less than current height.
+    ///
+    /// Stores built `with_pruning` remove the versions and records above target height
+    /// after the height is written, rolling back to the same height again removes the
+    /// ones left by a failure. Fails with `Error::ForkedAbove` if a live fork was
+    /// created above target height.
     pub fn rollback(&mut self, target_height: i64) -> Result<()> {
-        if target_height > self.height {
+        self.rewind(target_height)?;
+        if self.prune {
+            let keys = self.keys_above(target_height)?;
+            self.store.remove(keys)?;
+        }
+        Ok(())
+    }
+
+    /// Write target height and point the latest index back to it, versions above it
+    /// are kept.
+    pub(crate) fn rewind(&mut self, target_height: i64) -> Result<()> {
+        self.check_writable()?;
+        if target_height > self.height || target_height < self.base_height() {
             log::error!(
                 "Target height {} must less than current height {}",
                 target_height,
                 self.height
             );
-            Err(Error::HeightError)
+            Err(Error::HeightOutOfRange {
+                requested: target_height,
+                current: self.height,
+            })
         } else {
-            self.write_height(target_height, None)
+            self.check_forks_below(target_height)?;
+            let operations = if self.latest_index {
+                Some(self.rollback_latest_index(target_height)?)
+            } else {
+                None
+            };
+            self.merkle.rollback(target_height)?;
+            self.write_height(target_height, operations)
         }
     }
 
-    pub(crate) fn storage_key(&self, key: &[u8]) -> Vec<u8> {
-        utils::storage_key(&self.namespace, &key, self.height)
+    /// Views returned by `at` don't write.
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        if self.view {
+            Err(Error::ReadOnlyView {
+                height: self.height,
+            })
+        } else {
+            Ok(())
+        }
     }
 
-    pub(crate) fn storage_tuple_key(&self, key: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        (
-            utils::storage_key(&self.namespace, &key, 0),
-            utils::storage_key(&self.namespace, &key, self.height),
-        )
+    /// Keys of the versions, merkle, commit and statistics records of the namespace
+    /// above height.
+    fn keys_above(&self, height: i64) -> Result<Vec<Vec<u8>>> {
+        let mut keys = Vec::new();
+
+        for suffix in VERSION_RANGES {
+            let begin_key = [self.namespace.as_str(), suffix].concat().into_bytes();
+            let mut end_key = begin_key.clone();
+            end_key.push(u8::MAX);
+            for (key, _) in self.store.range(&begin_key, &end_key)? {
+                if utils::storage_key_height(&key).is_some_and(|h| h > height) {
+                    keys.push(key.to_vec());
+                }
+            }
+        }
+
+        for record_key in [utils::merkle_key, utils::commit_key] {
+            let begin_key = record_key(&self.namespace, height + 1);
+            let end_key = record_key(&self.namespace, i64::MAX);
+            for (key, _) in self.store.range(&begin_key, &end_key)? {
+                keys.push(key.to_vec());
+            }
+        }
+        keys.extend(self.stats_keys_above(height)?);
+
+        Ok(keys)
     }
 
-    /// Commit this snapshot.
-    pub fn commit(&mut self) -> Result<i64> {
+    /// Point index entries newer than target height back to the latest version at target height.
+    ///
+    /// Entries without older version are pointed at height 0.
+    fn rollback_latest_index(&self, target_height: i64) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
         let mut operations = Vec::new();
 
-        let mut merkle_operations = Vec::new();
+        let (begin_key, end_key) = utils::latest_key_range(&self.namespace);
+        for (index_key, bytes) in self.store.range(&begin_key, &end_key)? {
+            let latest = StoreHeight::from_bytes(&bytes).map_err(|e| e.with_key(&index_key))?;
+            if latest.height <= target_height {
+                continue;
+            }
 
-        log::debug!("Snapshot Cache: {:?}", self.value);
+            let key = hex::decode(&index_key[begin_key.len()..]).map_err(|e| Error::Corrupted {
+                key: index_key.to_vec(),
+                reason: e.to_string(),
+            })?;
+            let lower_key = utils::storage_key(&self.namespace, &key, 0);
+            let upper_key = utils::storage_key(&self.namespace, &key, target_height);
 
-        for (k, v) in self.value.operations()? {
-            let key_bytes = self.storage_key(&k);
-            let store_value = StoreValue {
-                operation: v.clone(),
+            let height = match self.store.range(&lower_key, &upper_key)?.next_back() {
+                Some((k, _)) => utils::storage_key_height(&k).unwrap_or(0),
+                None => 0,
             };
-            operations.push((key_bytes, store_value.to_bytes()?));
-            merkle_operations.push((k, v));
+            let store_height = StoreHeight { height };
+            operations.push((index_key.to_vec(), store_height.to_bytes()?));
+        }
+
+        Ok(operations)
+    }
+
+    /// Get the storage key and record of the latest version of key visible at height,
+    /// falling back to the ancestors of a fork.
+    pub(crate) fn get_version(
+        &self,
+        key: &[u8],
+        height: i64,
+    ) -> Result<Option<(CowBytes<'_>, CowBytes<'_>)>> {
+        self.find_version(&self.lineage(&self.namespace, height), key)
+    }
+
+    /// Get the latest version of key in namespaces of a lineage, newest first.
+    fn find_version(
+        &self,
+        lineage: &[(String, i64)],
+        key: &[u8],
+    ) -> Result<Option<(CowBytes<'_>, CowBytes<'_>)>> {
+        for (namespace, height) in lineage.iter().rev() {
+            let begin_key = utils::storage_key(namespace, key, 0);
+            let end_key = utils::storage_key(namespace, key, *height);
+            if let Some(record) = self.store.range(&begin_key, &end_key)?.next_back() {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the latest version of key visible at current height.
+    pub(crate) fn get_latest(&self, key: &[u8]) -> Result<Option<CowBytes<'_>>> {
+        if self.latest_index {
+            let index_key = utils::latest_key(&self.namespace, key);
+            if let Some(bytes) = self.store.get(&index_key)? {
+                let latest = StoreHeight::from_bytes(&bytes).map_err(|e| e.with_key(&index_key))?;
+                // Rolled back before its first version, only ancestors hold it.
+                if latest.height == 0 {
+                    let lineage = self.lineage(&self.namespace, self.height);
+                    let ancestors = &lineage[..lineage.len() - 1];
+                    return Ok(self.find_version(ancestors, key)?.map(|(_, v)| v));
+                }
+                if latest.height <= self.height {
+                    let key = utils::storage_key(&self.namespace, key, latest.height);
+                    return self.store.get(&key);
+                }
+            }
+        }
+
+        Ok(self.get_version(key, self.height)?.map(|(_, v)| v))
+    }
+
+    /// Get the latest operation of key visible at current height.
+    pub(crate) fn get_latest_operation_bytes(&self, key: &[u8]) -> Result<Option<OperationBytes>> {
+        if let Some(bytes) = self.get_latest(key)? {
+            let value = StoreValue::from_bytes(&bytes).map_err(|e| e.with_key(key))?;
+            Ok(Some(value.operation))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get and decode the latest operation of key visible at current height.
+    ///
+    /// Key is the one returned by `Model::operations`.
+    pub fn get_latest_operation<T>(&self, key: &[u8]) -> Result<Option<Operation<T>>>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        if let Some(operation) = self.get_latest_operation_bytes(key)? {
+            let operation = Operation::from_bytes(&operation).map_err(|e| e.with_key(key))?;
+            Ok(Some(operation))
+        } else {
+            Ok(None)
         }
+    }
 
-        // incr current height
-        self.write_height(self.height + 1, Some(operations))?;
+    /// Scan the latest operation of all keys visible at current height.
+    ///
+    /// This walks all versions of the namespace, use it for iteration only.
+    pub(crate) fn scan_operations(&self) -> Result<BTreeMap<Vec<u8>, OperationBytes>> {
+        self.scan_prefix_operations(&[])
+    }
 
-        log::debug!("Start Compute merkle");
-        self.merkle.insert(&mut self.store, &merkle_operations)?;
+    /// Scan the latest operation of keys starting with prefix visible at current height.
+    pub(crate) fn scan_prefix_operations(
+        &self,
+        prefix: &[u8],
+    ) -> Result<BTreeMap<Vec<u8>, OperationBytes>> {
+        let (begin_key, end_key) = utils::storage_key_range(&self.namespace, prefix);
+        self.scan_range_operations(&self.namespace, &begin_key, &end_key)
+    }
 
-        log::debug!("Sync snapshot success in height: {}", self.height);
+    /// Scan the latest operation of storage keys in `[begin_key, end_key]` of namespace
+    /// visible at current height.
+    pub(crate) fn scan_range_operations(
+        &self,
+        namespace: &str,
+        begin_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<BTreeMap<Vec<u8>, OperationBytes>> {
+        let mut operations = BTreeMap::new();
+
+        // Versions of forks override the ones of their ancestors.
+        for (branch, visible) in self.lineage(namespace, self.height) {
+            let begin_key = fork::rebase_key(begin_key, namespace, &branch);
+            let end_key = fork::rebase_key(end_key, namespace, &branch);
+            for (store_key, bytes) in self.store.range(&begin_key, &end_key)? {
+                let (key, height) =
+                    utils::parse_storage_key(&branch, &store_key).ok_or_else(|| {
+                        Error::Corrupted {
+                            key: store_key.to_vec(),
+                            reason: "invalid storage key".to_string(),
+                        }
+                    })?;
+
+                // Versions of one key are in ascending order.
+                if height <= visible {
+                    let value =
+                        StoreValue::from_bytes(&bytes).map_err(|e| e.with_key(&store_key))?;
+                    operations.insert(key, value.operation);
+                }
+            }
+        }
 
-        Ok(self.height)
+        Ok(operations)
+    }
+
+    /// Commit this snapshot.
+    pub fn commit(&mut self) -> Result<i64> {
+        self.commit_inner(None)
+    }
+
+    pub(crate) fn commit_inner(&mut self, metadata: Option<CommitMetadata>) -> Result<i64> {
+        let mut staged = self.stage_commit(metadata)?;
+        match self.apply_commit(&mut staged) {
+            Ok(height) => {
+                self.notify_commit(&staged);
+                Ok(height)
+            }
+            Err(e) => {
+                // A failed abort leaves the store apart from its cache, it's reported
+                // before the error of the commit.
+                log::error!("Commit failed: {}", e);
+                self.abort_commit(staged)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Take operations of the next commit at `height` from cache, including deletions
+    /// of expired keys, with expire heights of its keys.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn take_batch(
+        &self,
+        value: &mut V,
+        height: i64,
+    ) -> Result<(Vec<(Vec<u8>, OperationBytes)>, Vec<(Vec<u8>, i64)>)> {
+        value.prepare_commit(|k| self.get_latest_operation_bytes(k))?;
+        let expirations = value.expirations()?;
+        let mut batch = value.operations()?;
+        let expired = self.expire_operations(height, &batch)?;
+        batch.extend(expired);
+        Ok((batch, expirations))
     }
 
     pub fn root(&self) -> Result<digest::Output<M::Digest>> {
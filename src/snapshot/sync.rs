@@ -0,0 +1,530 @@
+//!
+//! State sync snapshot
+//!
+//! A snapshot holds the state of namespaces at a committed height: the latest
+//! version of each key visible at that height with its original height, the type
+//! code, the merkle record and the commit record. Records are framed as
+//! `len(key) ++ key ++ len(value) ++ value` with `u32` big endian lengths, and the
+//! stream is cut into chunks of fixed size, hashed in the manifest. Chunks are read
+//! from the backend one at a time, the writer computes the manifest in a first pass.
+//!
+//! Chunks are applied in order into an empty backend. Current height is written
+//! only after all chunks are applied, so a failed import leaves no loadable store.
+//!
+//! Chunks are authenticated by their hashes in the manifest only, so the manifest
+//! must be trusted, e.g. its `root` agreed by consensus. Roots of append only merkle
+//! trees depend on the whole history and can't be recomputed from the state, the
+//! import only checks that the merkle records carried by the chunks match the
+//! manifest roots.
+//!
+//! Only the latest version of each key is exported, after import `at(height)` below
+//! the snapshot height returns incomplete state.
+//!
+//! This matches the `ListSnapshots`/`OfferSnapshot`/`ApplySnapshotChunk` flow of
+//! Tendermint: the manifest is offered, then chunks are applied one by one.
+
+use core::{iter, marker::PhantomData, mem};
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use digest::Digest;
+
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::Store, merkle::Merkle, model::Model, CowBytes, Error, Operation, Result};
+
+use super::{utils, FromStoreBytes, SnapshotableStorage, StoreHeight, StoreValue, ToStoreBytes};
+
+/// Format of records in chunks.
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// Default chunk size, 1 MiB.
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1 << 20;
+
+/// Describe a snapshot, shipped before its chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub height: i64,
+    pub format: u32,
+    /// Size of all chunks but the last one.
+    pub chunk_size: u64,
+    pub namespaces: Vec<String>,
+    /// Merkle root of each namespace at height.
+    pub roots: Vec<Vec<u8>>,
+    /// Hash of each chunk.
+    pub chunks: Vec<Vec<u8>>,
+    /// Root of the only namespace, or hash of all roots in order.
+    pub root: Vec<u8>,
+}
+
+/// Manifest and chunks of a snapshot, held in memory.
+///
+/// Use `SnapshotWriter::manifest` and `SnapshotWriter::chunks` to stream them instead.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub manifest: SnapshotManifest,
+    pub chunks: Vec<Vec<u8>>,
+}
+
+/// Root of a snapshot over namespace roots.
+fn snapshot_root<D: Digest>(roots: &[Vec<u8>]) -> Vec<u8> {
+    if let [root] = roots {
+        return root.clone();
+    }
+
+    let mut hasher = D::new();
+    for root in roots {
+        hasher.update(root);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Records of a snapshot in order, read lazily.
+type Records<'a> = Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>;
+
+/// Latest version visible in one namespace of a lineage, as `(key, height, record)`.
+type Versions<'a> = Box<dyn Iterator<Item = Result<(Vec<u8>, i64, Vec<u8>)>> + 'a>;
+
+/// Collect storages at one height into a snapshot.
+pub struct SnapshotWriter<'a, D: Digest> {
+    height: i64,
+    chunk_size: usize,
+    namespaces: Vec<String>,
+    roots: Vec<Vec<u8>>,
+    sources: Vec<Box<dyn Fn() -> Records<'a> + 'a>>,
+    marker: PhantomData<D>,
+}
+
+impl<'a, D: Digest> SnapshotWriter<'a, D> {
+    pub fn new(height: i64) -> Self {
+        Self::with_chunk_size(height, SNAPSHOT_CHUNK_SIZE)
+    }
+
+    /// Chunk size is at least 1.
+    pub fn with_chunk_size(height: i64, chunk_size: usize) -> Self {
+        Self {
+            height,
+            chunk_size: chunk_size.max(1),
+            namespaces: Vec::new(),
+            roots: Vec::new(),
+            sources: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Add the state of a storage at height, records are read by `chunks`.
+    ///
+    /// Secondary indexes are exported if the storage maintains them.
+    pub fn add<S, M, V>(&mut self, storage: &'a SnapshotableStorage<S, M, V>) -> Result<()>
+    where
+        S: Store,
+        M: Merkle<Digest = D>,
+        V: Model,
+    {
+        let height = self.height;
+        if height < 0 || height > storage.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: storage.height,
+            });
+        }
+        // Roots below the fork height are recorded by ancestors.
+        let mut merkle = M::new(storage.record_namespace(height), 0);
+        merkle.rollback(height)?;
+        let root = merkle.root(&storage.store)?;
+        self.namespaces.push(storage.namespace.clone());
+        self.roots.push(root.to_vec());
+        self.sources
+            .push(Box::new(move || match storage.snapshot_records(height) {
+                Ok(records) => records,
+                Err(e) => Box::new(iter::once(Err(e))),
+            }));
+        Ok(())
+    }
+
+    /// Chunks of the snapshot, read from the backends of the storages on demand.
+    pub fn chunks(&self) -> SnapshotChunks<'_> {
+        SnapshotChunks {
+            records: Box::new(self.sources.iter().flat_map(|source| source())),
+            chunk_size: self.chunk_size,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Manifest of the snapshot, reads all chunks once to hash them.
+    pub fn manifest(&self) -> Result<SnapshotManifest> {
+        let mut chunks = Vec::new();
+        for chunk in self.chunks() {
+            chunks.push(D::digest(&chunk?).to_vec());
+        }
+        Ok(self.build_manifest(chunks))
+    }
+
+    /// Manifest and all chunks, held in memory.
+    pub fn finish(self) -> Result<Snapshot> {
+        let chunks = self.chunks().collect::<Result<Vec<_>>>()?;
+        let hashes = chunks.iter().map(|c| D::digest(c).to_vec()).collect();
+        Ok(Snapshot {
+            manifest: self.build_manifest(hashes),
+            chunks,
+        })
+    }
+
+    fn build_manifest(&self, chunks: Vec<Vec<u8>>) -> SnapshotManifest {
+        SnapshotManifest {
+            height: self.height,
+            format: SNAPSHOT_FORMAT,
+            chunk_size: self.chunk_size as u64,
+            root: snapshot_root::<D>(&self.roots),
+            namespaces: self.namespaces.clone(),
+            roots: self.roots.clone(),
+            chunks,
+        }
+    }
+}
+
+/// Chunks of a snapshot, see `SnapshotWriter::chunks`.
+pub struct SnapshotChunks<'a> {
+    records: Records<'a>,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<'a> Iterator for SnapshotChunks<'a> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffer.len() < self.chunk_size {
+            match self.records.next() {
+                Some(Ok((key, value))) => {
+                    for bytes in [key, value] {
+                        self.buffer
+                            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                        self.buffer.extend_from_slice(&bytes);
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let rest = match self.buffer.len() > self.chunk_size {
+            true => self.buffer.split_off(self.chunk_size),
+            false => Vec::new(),
+        };
+        Some(Ok(mem::replace(&mut self.buffer, rest)))
+    }
+}
+
+/// Apply chunks of a snapshot into an empty backend.
+pub struct SnapshotImporter<'a, S, M>
+where
+    S: Store,
+    M: Merkle,
+{
+    store: &'a mut S,
+    manifest: &'a SnapshotManifest,
+    next: usize,
+    buffer: Vec<u8>,
+    marker: PhantomData<M>,
+}
+
+impl<'a, S, M> SnapshotImporter<'a, S, M>
+where
+    S: Store,
+    M: Merkle,
+{
+    /// Check the manifest and that its namespaces are empty in store.
+    pub fn new(store: &'a mut S, manifest: &'a SnapshotManifest) -> Result<Self> {
+        if manifest.format != SNAPSHOT_FORMAT {
+            return Err(invalid(format!("unknown format {}", manifest.format)));
+        }
+        if manifest.namespaces.len() != manifest.roots.len() {
+            return Err(invalid("namespaces and roots don't match".to_string()));
+        }
+        if snapshot_root::<M::Digest>(&manifest.roots) != manifest.root {
+            return Err(Error::SnapshotRootMismatch);
+        }
+
+        for namespace in manifest.namespaces.iter() {
+            if store.get(&utils::type_key(namespace))?.is_some()
+                || store.get(&utils::current_height_key(namespace))?.is_some()
+            {
+                return Err(Error::StoreNotEmpty {
+                    namespace: namespace.clone(),
+                });
+            }
+        }
+
+        Ok(Self {
+            store,
+            manifest,
+            next: 0,
+            buffer: Vec::new(),
+            marker: PhantomData,
+        })
+    }
+
+    /// Verify and write a chunk, chunks must be applied in order.
+    pub fn apply_chunk(&mut self, index: usize, chunk: &[u8]) -> Result<()> {
+        if index != self.next {
+            return Err(invalid(format!(
+                "chunk {} applied out of order, expected {}",
+                index, self.next
+            )));
+        }
+        let hash = self
+            .manifest
+            .chunks
+            .get(index)
+            .ok_or_else(|| invalid(format!("chunk {} out of range", index)))?;
+        if M::Digest::digest(chunk)[..] != hash[..] {
+            return Err(invalid(format!("chunk {} hash mismatch", index)));
+        }
+        if index + 1 < self.manifest.chunks.len() && chunk.len() as u64 != self.manifest.chunk_size
+        {
+            return Err(invalid(format!("chunk {} size mismatch", index)));
+        }
+
+        self.buffer.extend_from_slice(chunk);
+        let mut batch = Vec::new();
+        let mut offset = 0;
+        while let Some((key, value, end)) = read_record(&self.buffer, offset) {
+            if !self.owns(key) {
+                return Err(invalid(format!(
+                    "record {} out of snapshot namespaces",
+                    hex::encode(key)
+                )));
+            }
+            batch.push((key.to_vec(), value.to_vec()));
+            offset = end;
+        }
+        self.buffer.drain(..offset);
+
+        self.store.execute(batch)?;
+        self.next += 1;
+        Ok(())
+    }
+
+    /// Check the merkle records of the chunks against the manifest roots, then write
+    /// current height of each namespace.
+    pub fn finish(self) -> Result<()> {
+        if self.next != self.manifest.chunks.len() {
+            return Err(invalid(format!(
+                "{} of {} chunks applied",
+                self.next,
+                self.manifest.chunks.len()
+            )));
+        }
+        if !self.buffer.is_empty() {
+            return Err(invalid("truncated record".to_string()));
+        }
+
+        let height = self.manifest.height;
+        let mut batch = Vec::new();
+        for (namespace, root) in self.manifest.namespaces.iter().zip(&self.manifest.roots) {
+            if M::new(namespace, height).root(self.store)?[..] != root[..] {
+                return Err(Error::SnapshotRootMismatch);
+            }
+            let store_height = StoreHeight { height };
+            batch.push((
+                utils::current_height_key(namespace),
+                store_height.to_bytes()?,
+            ));
+        }
+        self.store.execute(batch)
+    }
+
+    fn owns(&self, key: &[u8]) -> bool {
+        self.manifest.namespaces.iter().any(|namespace| {
+            key.len() > namespace.len()
+                && key.starts_with(namespace.as_bytes())
+                && key[namespace.len()] == b'-'
+        })
+    }
+}
+
+/// Verify and write all chunks of a snapshot into an empty backend.
+///
+/// Heights below the snapshot height only hold the keys still visible at it.
+pub fn import_snapshot<S, M, I>(store: &mut S, manifest: &SnapshotManifest, chunks: I) -> Result<()>
+where
+    S: Store,
+    M: Merkle,
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut importer = SnapshotImporter::<S, M>::new(store, manifest)?;
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        importer.apply_chunk(index, chunk.as_ref())?;
+    }
+    importer.finish()
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidSnapshot(reason)
+}
+
+/// Read a record at offset, return it with the end offset if complete.
+fn read_record(buffer: &[u8], offset: usize) -> Option<(&[u8], &[u8], usize)> {
+    let (key, offset) = read_bytes(buffer, offset)?;
+    let (value, offset) = read_bytes(buffer, offset)?;
+    Some((key, value, offset))
+}
+
+fn read_bytes(buffer: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = buffer.get(offset..offset + 4)?;
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    let begin = offset + 4;
+    Some((buffer.get(begin..begin + len)?, begin + len))
+}
+
+/// Snapshot export
+impl<S, M, V> SnapshotableStorage<S, M, V>
+where
+    S: Store,
+    M: Merkle,
+    V: Model,
+{
+    /// Export the state of this store at a committed height, held in memory.
+    ///
+    /// Use `SnapshotWriter` to stream the chunks instead.
+    pub fn export_snapshot(&self, height: i64) -> Result<Snapshot> {
+        let mut writer = SnapshotWriter::<M::Digest>::new(height);
+        writer.add(self)?;
+        writer.finish()
+    }
+
+    /// Records of this store in a snapshot at a committed height.
+    fn snapshot_records(&self, height: i64) -> Result<Records<'_>> {
+        if height < 0 || height > self.height {
+            return Err(Error::HeightOutOfRange {
+                requested: height,
+                current: self.height,
+            });
+        }
+
+        let type_key = utils::type_key(&self.namespace);
+        let head = self
+            .store
+            .get(&type_key)?
+            .map(|bytes| Ok((type_key, bytes.to_vec())));
+
+        let mut records: Records<'_> = Box::new(head.into_iter());
+        for sub in self.versioned_namespaces() {
+            records = Box::new(records.chain(self.latest_versions(sub, height)?));
+        }
+
+        let owner = self.record_namespace(height);
+        let mut tail = Vec::new();
+        for record_key in [utils::merkle_key, utils::commit_key] {
+            if let Some(bytes) = self.store.get(&record_key(owner, height))? {
+                tail.push(Ok((record_key(&self.namespace, height), bytes.to_vec())));
+            }
+        }
+        Ok(Box::new(records.chain(tail)))
+    }
+
+    /// Raw record of the latest update of each key in namespace visible at height,
+    /// in key order.
+    ///
+    /// Versions read from the ancestors of a fork are keyed in the namespace of the fork.
+    fn latest_versions(&self, namespace: String, height: i64) -> Result<Records<'_>> {
+        let mut branches = Vec::new();
+        for (branch, visible) in self.lineage(&namespace, height) {
+            let (begin_key, end_key) = utils::storage_key_range(&branch, []);
+            let range = self.store.range(&begin_key, &end_key)?;
+            let versions: Versions<'_> = Box::new(latest_visible(range, branch, visible));
+            branches.push(versions.peekable());
+        }
+
+        Ok(Box::new(iter::from_fn(move || loop {
+            // Smallest key of all branches, errors first.
+            let mut min: Option<Vec<u8>> = None;
+            for versions in branches.iter_mut() {
+                match versions.peek() {
+                    Some(Ok((key, _, _))) if min.as_ref().is_none_or(|min| key < min) => {
+                        min = Some(key.clone());
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) => {
+                        if let Some(Err(e)) = versions.next() {
+                            return Some(Err(e));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            let min = min?;
+
+            // Versions of forks override the ones of their ancestors.
+            let mut latest = None;
+            for versions in branches.iter_mut() {
+                if matches!(versions.peek(), Some(Ok((key, _, _))) if *key == min) {
+                    latest = versions.next();
+                }
+            }
+            let (key, version, bytes) = latest?.ok()?;
+
+            let store_key = utils::storage_key(&namespace, &key, version);
+            match StoreValue::from_bytes(&bytes) {
+                Ok(value) => {
+                    if let Operation::Update(_) = value.operation {
+                        return Some(Ok((store_key, bytes)));
+                    }
+                }
+                Err(e) => return Some(Err(e.with_key(&store_key))),
+            }
+        })))
+    }
+}
+
+/// Latest version of each key in the range of a namespace with height at most `visible`.
+fn latest_visible<'a, R>(
+    range: R,
+    namespace: String,
+    visible: i64,
+) -> impl Iterator<Item = Result<(Vec<u8>, i64, Vec<u8>)>> + 'a
+where
+    R: Iterator<Item = (CowBytes<'a>, CowBytes<'a>)> + 'a,
+{
+    let mut range = range.peekable();
+    iter::from_fn(move || loop {
+        let (store_key, bytes) = range.next()?;
+        let (key, height) = match utils::parse_storage_key(&namespace, &store_key) {
+            Some(parsed) => parsed,
+            None => {
+                return Some(Err(Error::Corrupted {
+                    key: store_key.to_vec(),
+                    reason: "invalid storage key".to_string(),
+                }))
+            }
+        };
+
+        // Versions of one key are in ascending order.
+        let mut latest = (height <= visible).then(|| (height, bytes.to_vec()));
+        while let Some((next_key, next_bytes)) = range.peek() {
+            match utils::parse_storage_key(&namespace, next_key) {
+                Some((next, height)) if next == key => {
+                    if height <= visible {
+                        latest = Some((height, next_bytes.to_vec()));
+                    }
+                    range.next();
+                }
+                _ => break,
+            }
+        }
+
+        if let Some((height, bytes)) = latest {
+            return Some(Ok((key, height, bytes)));
+        }
+    })
+}
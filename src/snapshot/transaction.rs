@@ -1,8 +1,24 @@
 //!
 //! Transaction Middleware
 //!
+//! Transactions count reads leaving their cache and writes into it, see `metrics`.
+//! A meter set by `with_meter` sees each access before it is counted and can abort
+//! it, e.g. to charge gas. Clones of a transaction share its counts, so accesses of a
+//! fork are counted by the transaction it's merged into.
+//!
+//! Counts are kept in an `Arc<Mutex>` with `std`. Without `std` they're kept in an
+//! `Rc<RefCell>`, so a transaction is neither `Send` nor `Sync`.
+
+use core::ops::DerefMut;
+
+use alloc::{collections::BTreeSet, sync::Arc, vec::Vec};
+
+#[cfg(feature = "cbor")]
+use serde::Serialize;
 
-use crate::{backend::Store, merkle::Merkle, model::Model, SnapshotableStorage};
+use crate::{
+    backend::Store, merkle::Merkle, model::Model, utils::cbor_encode, Result, SnapshotableStorage,
+};
 
 pub trait Forkable {
     type Cache;
@@ -12,6 +28,48 @@ pub trait Forkable {
     fn merge(&mut self, v: Self::Cache);
 }
 
+/// Counts of a transaction.
+///
+/// Bytes are the encoded key plus the encoded value of each access. Reads are
+/// counted when they miss the transaction cache, `lookups` when they reach the
+/// backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxMetrics {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Count of distinct keys read or written.
+    pub keys_touched: u64,
+    pub lookups: u64,
+}
+
+/// Access seen by the meter of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access<'k> {
+    Read {
+        key: &'k [u8],
+        bytes: u64,
+        lookup: bool,
+    },
+    Write {
+        key: &'k [u8],
+        bytes: u64,
+    },
+}
+
+pub(crate) type Meter = Arc<dyn Fn(&Access<'_>, &TxMetrics) -> Result<()> + Send + Sync>;
+
+#[derive(Default)]
+struct Recorder {
+    metrics: TxMetrics,
+    touched: BTreeSet<Vec<u8>>,
+}
+
+#[cfg(feature = "std")]
+type SharedRecorder = Arc<std::sync::Mutex<Recorder>>;
+
+#[cfg(not(feature = "std"))]
+type SharedRecorder = alloc::rc::Rc<core::cell::RefCell<Recorder>>;
+
 pub struct Transaction<'a, S, M, V>
 where
     S: Store,
@@ -20,6 +78,8 @@ where
 {
     pub store: &'a SnapshotableStorage<S, M, V>,
     pub value: V,
+    meter: Option<Meter>,
+    recorder: SharedRecorder,
 }
 
 impl<'a, S, M, V> Clone for Transaction<'a, S, M, V>
@@ -32,6 +92,8 @@ where
         Self {
             store: self.store,
             value: self.value.clone(),
+            meter: self.meter.clone(),
+            recorder: self.recorder.clone(),
         }
     }
 }
@@ -63,7 +125,76 @@ where
         Transaction {
             store,
             value: V::default(),
+            meter: None,
+            recorder: SharedRecorder::default(),
+        }
+    }
+
+    /// Call `f` with each access and the counts before it, an error aborts the access.
+    pub fn with_meter<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Access<'_>, &TxMetrics) -> Result<()> + Send + Sync + 'static,
+    {
+        self.meter = Some(Arc::new(f));
+        self
+    }
+
+    /// Counts of reads and writes so far, including the ones of clones.
+    pub fn metrics(&self) -> TxMetrics {
+        self.recorder().metrics
+    }
+
+    #[cfg(feature = "std")]
+    fn recorder(&self) -> impl DerefMut<Target = Recorder> + '_ {
+        // Meters run before the lock is taken, poison is ignored.
+        self.recorder.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn recorder(&self) -> impl DerefMut<Target = Recorder> + '_ {
+        self.recorder.borrow_mut()
+    }
+
+    /// Count a read of `value` under `key`, `lookup` if it reached the backend.
+    pub(crate) fn charge_read<T: Serialize>(
+        &self,
+        key: &[u8],
+        value: Option<&T>,
+        lookup: bool,
+    ) -> Result<()> {
+        let bytes = encoded_len(key, value)?;
+        self.charge(Access::Read { key, bytes, lookup })
+    }
+
+    /// Count a write of `value` under `key`, `None` for deletions.
+    pub(crate) fn charge_write<T: Serialize>(&self, key: &[u8], value: Option<&T>) -> Result<()> {
+        let bytes = encoded_len(key, value)?;
+        self.charge(Access::Write { key, bytes })
+    }
+
+    fn charge(&self, access: Access<'_>) -> Result<()> {
+        if let Some(meter) = &self.meter {
+            meter(&access, &self.metrics())?;
         }
+
+        let mut recorder = self.recorder();
+        let key = match access {
+            Access::Read { key, bytes, lookup } => {
+                recorder.metrics.bytes_read += bytes;
+                if lookup {
+                    recorder.metrics.lookups += 1;
+                }
+                key
+            }
+            Access::Write { key, bytes } => {
+                recorder.metrics.bytes_written += bytes;
+                key
+            }
+        };
+        if recorder.touched.insert(key.to_vec()) {
+            recorder.metrics.keys_touched += 1;
+        }
+        Ok(())
     }
 
     pub fn execute(&mut self, val: V) {
@@ -71,3 +202,11 @@ where
         self.value.merge(val)
     }
 }
+
+fn encoded_len<T: Serialize>(key: &[u8], value: Option<&T>) -> Result<u64> {
+    let value_len = match value {
+        Some(value) => cbor_encode(value)?.len(),
+        None => 0,
+    };
+    Ok((key.len() + value_len) as u64)
+}
@@ -2,7 +2,7 @@
 //! snapshot utils
 //!
 
-use alloc::{format, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
 
 /// Build current block high key
 pub fn current_height_key(namespace: &str) -> Vec<u8> {
@@ -16,14 +16,90 @@ pub fn storage_key<T: AsRef<[u8]>>(namespace: &str, key: T, height: i64) -> Vec<
     format!("{}-kw-{}-{:020}", namespace, hex::encode(key), height).into_bytes()
 }
 
+/// Build begin and end key of all storage keys starting with prefix in namespace
+pub fn storage_key_range<T: AsRef<[u8]>>(namespace: &str, prefix: T) -> (Vec<u8>, Vec<u8>) {
+    let begin_key = format!("{}-kw-{}", namespace, hex::encode(prefix)).into_bytes();
+    let mut end_key = begin_key.clone();
+    end_key.push(u8::MAX);
+    (begin_key, end_key)
+}
+
+/// Build namespace of a secondary index
+pub fn index_namespace(namespace: &str, name: &str) -> String {
+    format!("{}-ix-{}", namespace, name)
+}
+
+/// Build namespace of the expire schedule
+pub fn expire_namespace(namespace: &str) -> String {
+    format!("{}-ex", namespace)
+}
+
+/// Parse key and height from key built by `storage_key`
+pub fn parse_storage_key(namespace: &str, key: &[u8]) -> Option<(Vec<u8>, i64)> {
+    let prefix_len = namespace.len() + 4;
+    let height = storage_key_height(key)?;
+    // Skip `-` between key and height.
+    let hex_key = key.get(prefix_len..key.len() - 21)?;
+    Some((hex::decode(hex_key).ok()?, height))
+}
+
+/// Parse height from key built by `storage_key`
+pub fn storage_key_height(key: &[u8]) -> Option<i64> {
+    let height = key.get(key.len().checked_sub(20)?..)?;
+    core::str::from_utf8(height).ok()?.parse().ok()
+}
+
+/// Build latest version index key
+pub fn latest_key<T: AsRef<[u8]>>(namespace: &str, key: T) -> Vec<u8> {
+    format!("{}-lw-{}", namespace, hex::encode(key)).into_bytes()
+}
+
+/// Build begin and end key of all latest version index keys in namespace
+pub fn latest_key_range(namespace: &str) -> (Vec<u8>, Vec<u8>) {
+    let begin_key = format!("{}-lw-", namespace).into_bytes();
+    let mut end_key = begin_key.clone();
+    end_key.push(u8::MAX);
+    (begin_key, end_key)
+}
+
 /// Build type key
 pub fn type_key(namespace: &str) -> Vec<u8> {
     // TODO: use binary key to optimization performance
     format!("{}-ty", namespace).into_bytes()
 }
 
+/// Build commit record key
+pub fn commit_key(namespace: &str, height: i64) -> Vec<u8> {
+    format!("{}-cm-{:020}", namespace, height).into_bytes()
+}
+
+/// Build namespace of a fork, a name dropped and forked again gets a new generation
+pub fn fork_namespace(namespace: &str, name: &str, generation: u64) -> String {
+    format!("{}@{}.{}", namespace, name, generation)
+}
+
+/// Build fork registry key
+pub fn fork_key(namespace: &str, name: &str) -> Vec<u8> {
+    format!("{}-fk-{}", namespace, name).into_bytes()
+}
+
+/// Build key of the ancestors of a fork
+pub fn branches_key(namespace: &str) -> Vec<u8> {
+    format!("{}-br", namespace).into_bytes()
+}
+
+/// Build key of the fork promoted over namespace
+pub fn head_key(namespace: &str) -> Vec<u8> {
+    format!("{}-hd", namespace).into_bytes()
+}
+
 /// build merkle root key
 pub fn merkle_key(namespace: &str, height: i64) -> Vec<u8> {
     format!("{}-mr-{:020}", namespace, height).into_bytes()
 }
 //
+
+/// Build namespace statistics key
+pub fn stats_key(namespace: &str, height: i64) -> Vec<u8> {
+    format!("{}-st-{:020}", namespace, height).into_bytes()
+}
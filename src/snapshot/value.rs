@@ -1,5 +1,5 @@
 //!
-//!
+//! Wrappers of the values written to the storage layer
 //!
 
 use alloc::vec::Vec;
@@ -64,10 +64,18 @@ impl FromStoreBytes for StoreHeight {
     }
 }
 
+/// Layout of storage keys, bumped when the encoding of keys changes.
+///
+/// 0 is the cbor key encoding written before the ordered key encoding.
+pub const KEY_LAYOUT: u32 = 1;
+
 /// A cache layer worth of wrapping is stored at initialization time
 #[derive(Serialize, Deserialize)]
 pub struct StoreType {
     pub ty: u32,
+    /// Missing in records written before layouts were recorded.
+    #[serde(default)]
+    pub layout: u32,
 }
 
 #[cfg(feature = "cbor")]
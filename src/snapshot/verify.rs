@@ -0,0 +1,506 @@
+//!
+//! Integrity check
+//!
+//! `verify` walks all records of a backend. Namespaces are found by their `-ty` and
+//! `-ch` records, each record of a namespace is decoded by its tag, versions must not
+//! be above the current height of their namespace, and merkle roots are computed
+//! again height by height from the versions listed by the commit record of the height,
+//! in the order they were committed, and compared with the stored ones. Each height is
+//! chained from the stored root of the height below, so a missing or mismatched height
+//! doesn't fail the ones above it. Records owned by no namespace are orphaned.
+//!
+//! Versions above the current height with a commit record at their height are left
+//! by rollback, they are reported as stale without failing the check. The versions
+//! and merkle records of one height are held in memory while roots are computed.
+
+use core::fmt::{self, Display};
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use digest::{Digest, Output};
+
+#[cfg(feature = "cbor")]
+use serde::Serialize;
+
+use crate::{
+    backend::{MemoryBackend, Store},
+    merkle::{value::MerkleValue, Merkle},
+    Error, Operation, OperationBytes, Result,
+};
+
+use super::{
+    fork::{Branch, ForkRecord, Lineage},
+    metadata::CommitRecord,
+    utils,
+    value::{StoreType, KEY_LAYOUT},
+    FromStoreBytes, NamespaceStats, StoreHeight, StoreValue,
+};
+
+/// Result of `verify`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    pub namespaces: Vec<NamespaceReport>,
+    pub problems: Vec<Problem>,
+}
+
+/// Namespace found by `verify`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceReport {
+    pub namespace: String,
+    pub height: i64,
+    /// Records owned by the namespace and its sub namespaces.
+    pub records: u64,
+    /// Heights whose stored root matched the computed one.
+    pub roots: u64,
+}
+
+/// Record which failed a check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    pub key: Vec<u8>,
+    /// Namespace owning the record, `None` if orphaned.
+    pub namespace: Option<String>,
+    pub kind: ProblemKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ProblemKind {
+    /// Record can't be decoded.
+    Corrupted { reason: String },
+    /// Version is above the current height of its namespace.
+    AboveHeight { height: i64, current: i64 },
+    /// Version above the current height left by rollback, not a failure.
+    Stale { height: i64, current: i64 },
+    /// Merkle record of a height is missing.
+    MissingRoot { height: i64 },
+    /// Commit record of a height is missing.
+    MissingCommit { height: i64 },
+    /// Version listed by the commit record of its height is missing.
+    MissingVersion { height: i64 },
+    /// Stored root doesn't match the one computed from versions.
+    RootMismatch { height: i64 },
+    /// Record is owned by no namespace.
+    Orphaned,
+}
+
+impl VerifyReport {
+    /// No problem but stale versions was found.
+    pub fn is_ok(&self) -> bool {
+        self.problems
+            .iter()
+            .all(|p| matches!(p.kind, ProblemKind::Stale { .. }))
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = String::from_utf8_lossy(&self.key);
+        match &self.kind {
+            ProblemKind::Corrupted { reason } => write!(f, "corrupted record {}: {}", key, reason),
+            ProblemKind::AboveHeight { height, current } => write!(
+                f,
+                "version {} at height {} above current height {}",
+                key, height, current
+            ),
+            ProblemKind::Stale { height, current } => write!(
+                f,
+                "stale version {} at height {} above current height {}, left by rollback",
+                key, height, current
+            ),
+            ProblemKind::MissingRoot { height } => {
+                write!(f, "missing merkle record {} of height {}", key, height)
+            }
+            ProblemKind::MissingCommit { height } => {
+                write!(f, "missing commit record {} of height {}", key, height)
+            }
+            ProblemKind::MissingVersion { height } => {
+                write!(f, "missing version {} committed at height {}", key, height)
+            }
+            ProblemKind::RootMismatch { height } => {
+                write!(f, "merkle root {} of height {} mismatch", key, height)
+            }
+            ProblemKind::Orphaned => write!(f, "orphaned record {}", key),
+        }
+    }
+}
+
+/// Record kind, by the tag following its namespace.
+enum Record {
+    Type,
+    Height,
+    Branches,
+    Head,
+    Latest,
+    Fork,
+    Merkle(i64),
+    Commit,
+    Stats,
+    /// Version of the namespace or of a sub namespace.
+    Version {
+        height: i64,
+    },
+}
+
+/// Heights and bad roots of a namespace, collected while walking records.
+#[derive(Default)]
+struct Scan {
+    height: i64,
+    base: i64,
+    records: u64,
+    bad_roots: BTreeSet<i64>,
+}
+
+/// Check all records of a backend, merkle roots are computed with `M`.
+///
+/// Errors are reading errors of the backend, problems of records are reported.
+pub fn verify<S, M>(store: &S) -> Result<VerifyReport>
+where
+    S: Store,
+    M: Merkle,
+{
+    let mut report = VerifyReport::default();
+
+    // Longest first, so a namespace owns the records of namespaces it prefixes.
+    let mut namespaces = find_namespaces(store)?;
+    namespaces.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+    let mut scans: BTreeMap<String, Scan> = BTreeMap::new();
+    for namespace in namespaces.iter() {
+        let mut scan = Scan::default();
+        // A corrupted height is reported with the other records.
+        if let Some(bytes) = store.get(&utils::current_height_key(namespace))? {
+            if let Ok(height) = StoreHeight::from_bytes(&bytes) {
+                scan.height = height.height;
+            }
+        }
+        scans.insert(namespace.clone(), scan);
+    }
+
+    // Keys built by bs3 are utf-8.
+    for (key, bytes) in store.range(&[], &[u8::MAX])? {
+        let owner = core::str::from_utf8(&key).ok().and_then(|k| {
+            namespaces.iter().find_map(|namespace| {
+                let rest = k.strip_prefix(namespace.as_str())?;
+                Some((namespace, parse_record(namespace, rest, &key)?))
+            })
+        });
+        let (namespace, record) = match owner {
+            Some(owner) => owner,
+            None => {
+                report.problems.push(Problem {
+                    key: key.to_vec(),
+                    namespace: None,
+                    kind: ProblemKind::Orphaned,
+                });
+                continue;
+            }
+        };
+
+        let scan = scans.get_mut(namespace).ok_or_else(|| Error::Corrupted {
+            key: key.to_vec(),
+            reason: format!("namespace {} is not scanned", namespace),
+        })?;
+        scan.records += 1;
+        if let Err(kind) = check_record::<M>(scan, namespace, record, &bytes) {
+            // Rollback keeps the commit records of the heights it leaves.
+            let kind = match kind {
+                ProblemKind::AboveHeight { height, current }
+                    if store.get(&utils::commit_key(namespace, height))?.is_some() =>
+                {
+                    ProblemKind::Stale { height, current }
+                }
+                kind => kind,
+            };
+            report.problems.push(Problem {
+                key: key.to_vec(),
+                namespace: Some(namespace.clone()),
+                kind,
+            });
+        }
+    }
+
+    for (namespace, scan) in scans.iter() {
+        let roots = check_roots::<S, M>(store, namespace, scan, &mut report)?;
+        report.namespaces.push(NamespaceReport {
+            namespace: namespace.clone(),
+            height: scan.height,
+            records: scan.records,
+            roots,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Namespaces with type and height records.
+fn find_namespaces<S: Store>(store: &S) -> Result<Vec<String>> {
+    let mut namespaces = Vec::new();
+    for (key, _) in store.range(&[], &[u8::MAX])? {
+        let namespace = match key
+            .strip_suffix(b"-ty")
+            .and_then(|k| core::str::from_utf8(k).ok())
+        {
+            Some(namespace) => namespace,
+            None => continue,
+        };
+        // Names of forks can end with `-ty` too.
+        if store.get(&utils::current_height_key(namespace))?.is_some() {
+            namespaces.push(namespace.to_string());
+        }
+    }
+    Ok(namespaces)
+}
+
+/// Parse the rest of a key following its namespace.
+fn parse_record(namespace: &str, rest: &str, key: &[u8]) -> Option<Record> {
+    match rest {
+        "-ty" => return Some(Record::Type),
+        "-ch" => return Some(Record::Height),
+        "-br" => return Some(Record::Branches),
+        "-hd" => return Some(Record::Head),
+        _ => {}
+    }
+
+    if let Some(hex_key) = rest.strip_prefix("-lw-") {
+        return hex::decode(hex_key).ok().map(|_| Record::Latest);
+    }
+    if let Some(name) = rest.strip_prefix("-fk-") {
+        return (!name.is_empty()).then_some(Record::Fork);
+    }
+    for tag in ["-mr-", "-cm-", "-st-"] {
+        if let Some(height) = rest.strip_prefix(tag) {
+            let height: i64 = match height.len() {
+                20 => height.parse().ok()?,
+                _ => return None,
+            };
+            return Some(match tag {
+                "-mr-" => Record::Merkle(height),
+                "-cm-" => Record::Commit,
+                _ => Record::Stats,
+            });
+        }
+    }
+
+    // Hex keys and heights never contain the tag.
+    let suffix = rest.rfind("-kw-")?;
+    let sub = &rest[..suffix];
+    if !(sub.is_empty() || sub == "-ex" || sub.starts_with("-ix-")) {
+        return None;
+    }
+    let mut full = namespace.to_string();
+    full.push_str(sub);
+    let (_, height) = utils::parse_storage_key(&full, key)?;
+    Some(Record::Version { height })
+}
+
+/// Decode a record and check the height of a version.
+fn check_record<M: Merkle>(
+    scan: &mut Scan,
+    namespace: &str,
+    record: Record,
+    bytes: &[u8],
+) -> core::result::Result<(), ProblemKind> {
+    let reason = |e: Error| ProblemKind::Corrupted {
+        reason: e.to_string(),
+    };
+    match record {
+        Record::Type => {
+            let store_type = StoreType::from_bytes(bytes).map_err(reason)?;
+            if store_type.layout > KEY_LAYOUT {
+                return Err(reason(Error::LayoutMismatch {
+                    namespace: namespace.to_string(),
+                    expected: KEY_LAYOUT,
+                    found: store_type.layout,
+                }));
+            }
+        }
+        Record::Height | Record::Latest => {
+            StoreHeight::from_bytes(bytes).map_err(reason)?;
+        }
+        Record::Branches => {
+            let lineage = Lineage::from_bytes(bytes).map_err(reason)?;
+            scan.base = lineage.branches.last().map(|b| b.height).unwrap_or(0);
+        }
+        Record::Head => {
+            Branch::from_bytes(bytes).map_err(reason)?;
+        }
+        Record::Fork => {
+            ForkRecord::from_bytes(bytes).map_err(reason)?;
+        }
+        Record::Commit => {
+            let record = CommitRecord::from_bytes(bytes).map_err(reason)?;
+            if record.order.len() as u64 != record.keys {
+                return Err(ProblemKind::Corrupted {
+                    reason: format!(
+                        "commit record lists {} of {} keys",
+                        record.order.len(),
+                        record.keys
+                    ),
+                });
+            }
+        }
+        Record::Stats => {
+            NamespaceStats::from_bytes(bytes).map_err(reason)?;
+        }
+        Record::Merkle(height) => {
+            if let Err(reason) = decode_root::<M::Digest>(bytes) {
+                scan.bad_roots.insert(height);
+                return Err(ProblemKind::Corrupted { reason });
+            }
+        }
+        Record::Version { height } => {
+            StoreValue::from_bytes(bytes).map_err(reason)?;
+            if height > scan.height {
+                return Err(ProblemKind::AboveHeight {
+                    height,
+                    current: scan.height,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Root of a merkle record, checking its length.
+fn decode_root<D: Digest>(bytes: &[u8]) -> core::result::Result<Vec<u8>, String> {
+    let value = MerkleValue::from_bytes(bytes).map_err(|e| e.to_string())?;
+    let root = match Operation::<Vec<Vec<u8>>>::from_bytes(&value.operation) {
+        Ok(Operation::Update(hashs)) => hashs.last().cloned(),
+        Ok(Operation::Delete) => return Err("merkle record is deleted".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+    match root {
+        Some(root) if root.len() == D::output_size() => Ok(root),
+        Some(root) => Err(format!(
+            "merkle root of {} bytes, digest has {}",
+            root.len(),
+            D::output_size()
+        )),
+        None => Err("merkle record is empty".to_string()),
+    }
+}
+
+/// Compute roots of the heights committed by a namespace and compare them.
+fn check_roots<S, M>(
+    store: &S,
+    namespace: &str,
+    scan: &Scan,
+    report: &mut VerifyReport,
+) -> Result<u64>
+where
+    S: Store,
+    M: Merkle,
+{
+    // A fork chains its roots from the one copied at its base height.
+    let base = scan.base;
+    if scan.bad_roots.contains(&base) {
+        return Ok(0);
+    }
+    let mut previous = match base {
+        0 => None,
+        _ => store
+            .get(&utils::merkle_key(namespace, base))?
+            .map(|b| b.to_vec()),
+    };
+
+    let mut merkle = M::new(namespace, base);
+    let mut roots = 0;
+    for height in base + 1..=scan.height {
+        let key = utils::merkle_key(namespace, height);
+        let stored = match scan.bad_roots.contains(&height) {
+            true => None,
+            false => store.get(&key)?.map(|b| b.to_vec()),
+        };
+
+        let mut scratch = MemoryBackend::new();
+        if let Some(bytes) = previous.take() {
+            scratch.insert(utils::merkle_key(namespace, height - 1), bytes)?;
+        }
+        // Heights above a missing commit record chain from the stored root.
+        let batch = match commit_batch(store, namespace, height, report)? {
+            Some(batch) => batch,
+            None => {
+                previous = stored;
+                continue;
+            }
+        };
+        merkle.rollback(height - 1)?;
+        merkle.insert(&mut scratch, &batch)?;
+        let root = merkle.root(&scratch)?;
+        previous = match stored.clone() {
+            Some(bytes) => Some(bytes),
+            None => scratch.get(&key)?.map(|b| b.to_vec()),
+        };
+        if root == Output::<M::Digest>::default() || scan.bad_roots.contains(&height) {
+            continue;
+        }
+
+        let kind = match stored {
+            None => ProblemKind::MissingRoot { height },
+            Some(bytes) => match decode_root::<M::Digest>(&bytes) {
+                Ok(stored) if stored[..] == root[..] => {
+                    roots += 1;
+                    continue;
+                }
+                _ => ProblemKind::RootMismatch { height },
+            },
+        };
+        report.problems.push(Problem {
+            key,
+            namespace: Some(namespace.to_string()),
+            kind,
+        });
+    }
+    Ok(roots)
+}
+
+/// Versions of a height in the order they were committed, read by its commit record.
+///
+/// `None` if the commit record is missing or corrupted, versions which are missing or
+/// corrupted are skipped.
+#[allow(clippy::type_complexity)]
+fn commit_batch<S: Store>(
+    store: &S,
+    namespace: &str,
+    height: i64,
+    report: &mut VerifyReport,
+) -> Result<Option<Vec<(Vec<u8>, OperationBytes)>>> {
+    let key = utils::commit_key(namespace, height);
+    let record = match store.get(&key)? {
+        Some(bytes) => match CommitRecord::from_bytes(&bytes) {
+            Ok(record) => record,
+            // Reported while walking records.
+            Err(_) => return Ok(None),
+        },
+        None => {
+            report.problems.push(Problem {
+                key,
+                namespace: Some(namespace.to_string()),
+                kind: ProblemKind::MissingCommit { height },
+            });
+            return Ok(None);
+        }
+    };
+
+    let mut batch = Vec::with_capacity(record.order.len());
+    for key in record.order {
+        let store_key = utils::storage_key(namespace, &key, height);
+        match store.get(&store_key)? {
+            Some(bytes) => {
+                if let Ok(value) = StoreValue::from_bytes(&bytes) {
+                    batch.push((key, value.operation));
+                }
+            }
+            None => report.problems.push(Problem {
+                key: store_key,
+                namespace: Some(namespace.to_string()),
+                kind: ProblemKind::MissingVersion { height },
+            }),
+        }
+    }
+    Ok(Some(batch))
+}
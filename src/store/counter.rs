@@ -0,0 +1,42 @@
+use crate::{
+    merkle::Merkle,
+    model::{Counter, CounterValue},
+    Result, SnapshotableStorage, Store,
+};
+
+use super::utils::counter_utils;
+
+/// Defining the basic behavior of the counter application layer
+///
+/// `add` and `sub` only record deltas, they don't read committed value.
+pub trait CounterStore<N>
+where
+    N: CounterValue,
+{
+    /// Committed value with cached deltas applied.
+    fn get(&self) -> Result<N>;
+
+    fn add(&mut self, n: N) -> Result<()>;
+
+    fn sub(&mut self, n: N) -> Result<()>;
+}
+
+impl<S, M, N> CounterStore<N> for SnapshotableStorage<S, M, Counter<N>>
+where
+    N: CounterValue,
+    S: Store,
+    M: Merkle,
+{
+    fn get(&self) -> Result<N> {
+        let base = counter_utils::get_inner_value(self)?;
+        self.value.apply(base)
+    }
+
+    fn add(&mut self, n: N) -> Result<()> {
+        self.value.add(n)
+    }
+
+    fn sub(&mut self, n: N) -> Result<()> {
+        self.value.sub(n)
+    }
+}
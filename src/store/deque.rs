@@ -0,0 +1,125 @@
+use super::utils::deque_utils;
+use crate::{merkle::Merkle, model::Deque, Cow, Operation, Result, SnapshotableStorage, Store};
+use alloc::vec::{self, Vec};
+use core::fmt::Debug;
+use serde::{Deserialize, Serialize};
+
+pub trait DequeStore<T>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+{
+    fn push_back(&mut self, value: T) -> Result<()>;
+
+    fn push_front(&mut self, value: T) -> Result<()>;
+
+    fn pop_front(&mut self) -> Result<Option<T>>;
+
+    fn pop_back(&mut self) -> Result<Option<T>>;
+
+    fn front(&self) -> Result<Option<Cow<'_, T>>>;
+
+    fn len(&self) -> Result<u64>;
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Iterate members from front to back.
+    fn iter(&self) -> Result<vec::IntoIter<T>>;
+}
+
+impl<S, M, T> SnapshotableStorage<S, M, Deque<T>>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+    S: Store,
+    M: Merkle,
+{
+    pub(crate) fn head(&self) -> Result<i64> {
+        match self.value.head {
+            Some(head) => Ok(head),
+            None => deque_utils::get_inner_head(self),
+        }
+    }
+
+    pub(crate) fn tail(&self) -> Result<i64> {
+        match self.value.tail {
+            Some(tail) => Ok(tail),
+            None => deque_utils::get_inner_tail(self),
+        }
+    }
+
+    pub(crate) fn get_index(&self, index: i64) -> Result<Option<Cow<'_, T>>> {
+        match self.value.value.get(&index) {
+            Some(Operation::Update(v)) => Ok(Some(Cow::Borrowed(v))),
+            Some(Operation::Delete) => Ok(None),
+            None => Ok(deque_utils::get_inner_value(self, index)?.map(Cow::Owned)),
+        }
+    }
+}
+
+impl<S, M, T> DequeStore<T> for SnapshotableStorage<S, M, Deque<T>>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+    S: Store,
+    M: Merkle,
+{
+    fn push_back(&mut self, value: T) -> Result<()> {
+        let tail = self.tail()?;
+        self.value.value.insert(tail, Operation::Update(value));
+        self.value.tail = Some(tail + 1);
+        Ok(())
+    }
+
+    fn push_front(&mut self, value: T) -> Result<()> {
+        let head = self.head()? - 1;
+        self.value.value.insert(head, Operation::Update(value));
+        self.value.head = Some(head);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Result<Option<T>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        let head = self.head()?;
+        let res = self.get_index(head)?.map(|v| v.clone());
+        self.value.value.insert(head, Operation::Delete);
+        self.value.head = Some(head + 1);
+        Ok(res)
+    }
+
+    fn pop_back(&mut self) -> Result<Option<T>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        let tail = self.tail()? - 1;
+        let res = self.get_index(tail)?.map(|v| v.clone());
+        self.value.value.insert(tail, Operation::Delete);
+        self.value.tail = Some(tail);
+        Ok(res)
+    }
+
+    fn front(&self) -> Result<Option<Cow<'_, T>>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        self.get_index(self.head()?)
+    }
+
+    fn len(&self) -> Result<u64> {
+        Ok((self.tail()? - self.head()?) as u64)
+    }
+
+    fn iter(&self) -> Result<vec::IntoIter<T>> {
+        let mut values = Vec::new();
+        for index in self.head()?..self.tail()? {
+            if let Some(v) = self.get_index(index)? {
+                values.push(v.clone());
+            }
+        }
+        Ok(values.into_iter())
+    }
+}
@@ -1,5 +1,7 @@
 use core::fmt::Debug;
 
+use alloc::collections::btree_map;
+
 use crate::{
     merkle::Merkle, model::DoubleKeyMap, Cow, Operation, Result, SnapshotableStorage, Store,
 };
@@ -21,6 +23,12 @@ where
     fn insert(&mut self, key1: K1, key2: K2, value: V) -> Result<Option<V>>;
 
     fn remove(&mut self, key1: &K1, key2: &K2) -> Result<Option<V>>;
+
+    /// Iterate all entries under key1 in key2 order.
+    fn iter_prefix(&self, key1: &K1) -> Result<btree_map::IntoIter<K2, V>>;
+
+    /// Remove all entries under key1, return count of removed entries.
+    fn remove_prefix(&mut self, key1: &K1) -> Result<usize>;
 }
 
 impl<S, M, K1, K2, V> DoubleKeyMapStore<K1, K2, V>
@@ -34,7 +42,7 @@ where
 {
     fn get(&self, key1: &K1, key2: &K2) -> Result<Option<Cow<'_, V>>> {
         let key = &(key1.clone(), key2.clone());
-        return if let Some(operation) = self.value.value.value.get(key) {
+        if let Some(operation) = self.value.value.value.get(key) {
             match operation {
                 Operation::Update(v) => Ok(Some(Cow::Borrowed(v))),
                 Operation::Delete => Ok(None),
@@ -43,7 +51,7 @@ where
             Ok(Some(Cow::Owned(v)))
         } else {
             Ok(None)
-        };
+        }
     }
 
     fn get_mut(&mut self, key1: &K1, key2: &K2) -> Result<Option<&mut V>> {
@@ -92,4 +100,22 @@ where
 
         Ok(res)
     }
+
+    fn iter_prefix(&self, key1: &K1) -> Result<btree_map::IntoIter<K2, V>> {
+        let mut entries = doublekeymap_utils::inner_prefix_entries(self, key1)?;
+        doublekeymap_utils::apply_prefix_cache(&mut entries, &self.value, key1);
+        Ok(entries.into_iter())
+    }
+
+    fn remove_prefix(&mut self, key1: &K1) -> Result<usize> {
+        let mut count = 0;
+        for (key2, _) in self.iter_prefix(key1)? {
+            self.value
+                .value
+                .value
+                .insert((key1.clone(), key2), Operation::Delete);
+            count += 1;
+        }
+        Ok(count)
+    }
 }
@@ -0,0 +1,234 @@
+//!
+//! Secondary index of map store
+//!
+
+use core::{
+    fmt::Debug,
+    ops::{Bound, RangeBounds},
+};
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{
+    merkle::Merkle,
+    model::Map,
+    snapshot::{index::IndexOperations, utils, StoreValue, ToStoreBytes},
+    Operation, OperationBytes, OrderedKey, Result, SnapshotableStorage, Store,
+};
+
+use super::utils::map_utils;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+/// Secondary index on value of a map store, computed by `V -> IK`.
+///
+/// Entries are `(IK, K)` pairs written in the commit batch of the map, so index
+/// reads see the committed state at the store height, like `at(height)`.
+/// Values committed before the index was added are not indexed until `rebuild_index`.
+pub struct Index<V, IK> {
+    name: String,
+    f: Arc<dyn Fn(&V) -> IK + Send + Sync>,
+}
+
+impl<V, IK> Clone for Index<V, IK> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<V, IK> Index<V, IK> {
+    /// Create an index, name must be unique in its map store.
+    pub fn new<F>(name: &str, f: F) -> Self
+    where
+        F: Fn(&V) -> IK + Send + Sync + 'static,
+    {
+        Self {
+            name: String::from(name),
+            f: Arc::new(f),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<V, IK> Index<V, IK>
+where
+    V: Serialize + for<'de> Deserialize<'de>,
+    IK: OrderedKey,
+{
+    fn index_key(&self, operation: Option<&OperationBytes>) -> Result<Option<Vec<u8>>> {
+        match operation {
+            Some(operation @ Operation::Update(_)) => {
+                match Operation::<V>::from_bytes(operation)? {
+                    Operation::Update(v) => Ok(Some((self.f)(&v).to_key_bytes()?)),
+                    Operation::Delete => Ok(None),
+                }
+            }
+            Some(Operation::Delete) | None => Ok(None),
+        }
+    }
+}
+
+impl<V, IK> IndexOperations for Index<V, IK>
+where
+    V: Serialize + for<'de> Deserialize<'de>,
+    IK: OrderedKey,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn operations(
+        &self,
+        key: &[u8],
+        old: Option<&OperationBytes>,
+        new: &OperationBytes,
+    ) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        let old = self.index_key(old)?;
+        let new = self.index_key(Some(new))?;
+        let mut operations = Vec::new();
+        if old == new {
+            return Ok(operations);
+        }
+        // Encoded pair is the concatenation of both keys.
+        if let Some(mut old) = old {
+            old.extend_from_slice(key);
+            operations.push((old, Operation::Delete));
+        }
+        if let Some(mut new) = new {
+            new.extend_from_slice(key);
+            operations.push((new, Operation::Update(Vec::new())));
+        }
+        Ok(operations)
+    }
+}
+
+/// Secondary index methods of map store
+impl<S, M, K, V> SnapshotableStorage<S, M, Map<K, V>>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+    S: Store,
+    M: Merkle,
+{
+    /// Maintain a secondary index on commit.
+    pub fn with_index<IK>(mut self, index: &Index<V, IK>) -> Self
+    where
+        IK: OrderedKey + 'static,
+        V: 'static,
+    {
+        self.indexes.push(Arc::new(index.clone()));
+        self
+    }
+
+    /// Rewrite entries of an index from the values committed at current height.
+    ///
+    /// Missing entries are added and stale ones deleted at current height, so views
+    /// `at` lower heights keep the entries they had.
+    pub fn rebuild_index<IK>(&mut self, index: &Index<V, IK>) -> Result<()>
+    where
+        IK: OrderedKey,
+    {
+        let namespace = utils::index_namespace(&self.namespace, index.name());
+        let (begin_key, end_key) = utils::storage_key_range(&namespace, []);
+        let mut stale: BTreeSet<Vec<u8>> = self
+            .scan_range_operations(&namespace, &begin_key, &end_key)?
+            .into_iter()
+            .filter_map(|(entry, operation)| match operation {
+                Operation::Update(_) => Some(entry),
+                Operation::Delete => None,
+            })
+            .collect();
+
+        let mut operations = Vec::new();
+        for (key, operation) in self.scan_operations()? {
+            if let Some(mut entry) = index.index_key(Some(&operation))? {
+                entry.extend_from_slice(&key);
+                if !stale.remove(&entry) {
+                    operations.push((entry, Operation::Update(Vec::new())));
+                }
+            }
+        }
+        operations.extend(stale.into_iter().map(|entry| (entry, Operation::Delete)));
+
+        let mut batch = Vec::new();
+        for (entry, operation) in operations {
+            let store_value = StoreValue { operation };
+            batch.push((
+                utils::storage_key(&namespace, &entry, self.height),
+                store_value.to_bytes()?,
+            ));
+        }
+        self.store.execute(batch)
+    }
+
+    /// Committed entries whose index key equals `key`.
+    pub fn get_by_index<IK>(&self, index: &Index<V, IK>, key: &IK) -> Result<BTreeMap<K, V>>
+    where
+        IK: OrderedKey,
+    {
+        let prefix = key.to_key_bytes()?;
+        let namespace = utils::index_namespace(&self.namespace, index.name());
+        let (begin_key, end_key) = utils::storage_key_range(&namespace, &prefix);
+
+        let mut entries = BTreeMap::new();
+        for (entry, operation) in self.scan_range_operations(&namespace, &begin_key, &end_key)? {
+            if let Operation::Update(_) = operation {
+                let primary =
+                    K::from_key_bytes(&entry[prefix.len()..]).map_err(|e| e.with_key(&entry))?;
+                if let Some(v) = map_utils::get_inner_value(self, &primary)? {
+                    entries.insert(primary, v);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Committed entries whose index key is in range, in index key order.
+    pub fn range_by_index<IK, R>(&self, index: &Index<V, IK>, range: R) -> Result<Vec<(IK, K, V)>>
+    where
+        IK: Serialize + for<'de> Deserialize<'de>,
+        R: RangeBounds<IK>,
+    {
+        let namespace = utils::index_namespace(&self.namespace, index.name());
+        let (lowest, highest) = utils::storage_key_range(&namespace, []);
+
+        // Storage keys hex encode index keys, so their order is kept, and no
+        // storage key equals a bare prefix or ends with `u8::MAX`.
+        let begin_key = match range.start_bound() {
+            Bound::Included(k) => utils::storage_key_range(&namespace, k.to_key_bytes()?).0,
+            Bound::Excluded(k) => utils::storage_key_range(&namespace, k.to_key_bytes()?).1,
+            Bound::Unbounded => lowest,
+        };
+        let end_key = match range.end_bound() {
+            Bound::Included(k) => utils::storage_key_range(&namespace, k.to_key_bytes()?).1,
+            Bound::Excluded(k) => utils::storage_key_range(&namespace, k.to_key_bytes()?).0,
+            Bound::Unbounded => highest,
+        };
+        if begin_key > end_key {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for (entry, operation) in self.scan_range_operations(&namespace, &begin_key, &end_key)? {
+            if let Operation::Update(_) = operation {
+                let (key, primary) =
+                    <(IK, K)>::from_key_bytes(&entry).map_err(|e| e.with_key(&entry))?;
+                if let Some(v) = map_utils::get_inner_value(self, &primary)? {
+                    entries.push((key, primary, v));
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
@@ -19,6 +19,109 @@ where
     fn insert(&mut self, key: K, value: V) -> Result<Option<V>>;
 
     fn remove(&mut self, key: &K) -> Result<Option<V>>;
+
+    /// Insert a value which is removed by the first commit above `expire_height`.
+    ///
+    /// `insert` and `remove` of the key before commit drop the expiry, any later
+    /// committed write of the key cancels it.
+    fn insert_with_ttl(&mut self, key: K, value: V, expire_height: i64) -> Result<Option<V>>;
+
+    /// Get the entry of key for in-place manipulation.
+    ///
+    /// Committed value is pulled into cache once, it is written on commit only if
+    /// it was changed.
+    fn entry(&mut self, key: K) -> Result<MapEntry<'_, K, V>>;
+}
+
+/// A view into a single key of map cache, like `btree_map::Entry`.
+///
+/// Inserting into a vacant entry or removing it drops a pending expiry like
+/// `insert` and `remove`. A committed value read by the entry is only written if
+/// it is changed.
+pub struct MapEntry<'a, K, V>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    key: K,
+    map: &'a mut Map<K, V>,
+}
+
+impl<'a, K, V> MapEntry<'a, K, V>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+{
+    pub(crate) fn new(key: K, map: &'a mut Map<K, V>) -> Self {
+        Self { key, map }
+    }
+
+    /// Key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Whether this key has a value.
+    pub fn is_occupied(&self) -> bool {
+        matches!(self.map.value.get(&self.key), Some(Operation::Update(_)))
+    }
+
+    /// Insert `default` if vacant, return the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the result of `f` if vacant, return the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        if !self.is_occupied() {
+            self.map.expire.remove(&self.key);
+            self.map.loaded.remove(&self.key);
+        }
+        let operation = self.map.value.entry(self.key).or_insert(Operation::Delete);
+        match operation {
+            Operation::Update(v) => v,
+            Operation::Delete => insert_update(operation, f()),
+        }
+    }
+
+    /// Insert `V::default()` if vacant, return the value.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Modify the value if occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        if let Some(Operation::Update(v)) = self.map.value.get_mut(&self.key) {
+            f(v);
+        }
+        self
+    }
+
+    /// Remove the value, return it if occupied.
+    pub fn remove(self) -> Option<V> {
+        self.map.expire.remove(&self.key);
+        self.map.loaded.remove(&self.key);
+        let operation = self.map.value.insert(self.key, Operation::Delete);
+        match operation {
+            Some(Operation::Update(v)) => Some(v),
+            Some(Operation::Delete) | None => None,
+        }
+    }
+}
+
+/// Replace a deletion by an update of `value`, return the value of the update.
+fn insert_update<V: Clone>(operation: &mut Operation<V>, value: V) -> &mut V {
+    match operation {
+        Operation::Update(v) => v,
+        Operation::Delete => {
+            *operation = Operation::Update(value.clone());
+            // Returns the value of the update in one step.
+            insert_update(operation, value)
+        }
+    }
 }
 
 /// Implementing the middle and cache layers is the behavior of map
@@ -30,7 +133,7 @@ where
     M: Merkle,
 {
     fn get(&self, key: &K) -> Result<Option<Cow<'_, V>>> {
-        return if let Some(operation) = self.value.value.get(key) {
+        if let Some(operation) = self.value.value.get(key) {
             match operation {
                 Operation::Update(v) => Ok(Some(Cow::Borrowed(v))),
                 Operation::Delete => Ok(None),
@@ -39,7 +142,7 @@ where
             Ok(Some(Cow::Owned(v)))
         } else {
             Ok(None)
-        };
+        }
     }
 
     fn get_mut(&mut self, key: &K) -> Result<Option<&mut V>> {
@@ -48,8 +151,8 @@ where
         }
 
         if !self.value.value.contains_key(key) {
-            if let Some(operation) = map_utils::get_inner_operation(self, key)? {
-                self.value.value.insert(key.clone(), operation);
+            if let Some(Operation::Update(v)) = map_utils::get_inner_operation(self, key)? {
+                self.value.load(key.clone(), v)?;
             } else {
                 return Ok(None);
             }
@@ -64,11 +167,22 @@ where
 
     fn insert(&mut self, key: K, value: V) -> Result<Option<V>> {
         let operation = Operation::Update(value);
+        self.value.expire.remove(&key);
+        self.value.loaded.remove(&key);
         self.value.value.insert(key.clone(), operation);
         map_utils::get_inner_value(self, &key)
     }
 
+    fn insert_with_ttl(&mut self, key: K, value: V, expire_height: i64) -> Result<Option<V>> {
+        map_utils::check_expire_height(expire_height, self.height)?;
+        let res = self.insert(key.clone(), value)?;
+        self.value.expire.insert(key, expire_height);
+        Ok(res)
+    }
+
     fn remove(&mut self, key: &K) -> Result<Option<V>> {
+        self.value.expire.remove(key);
+        self.value.loaded.remove(key);
         let res = if let Some(op) = self.value.value.remove(key) {
             match op {
                 Operation::Update(v) => Some(v),
@@ -82,4 +196,14 @@ where
 
         Ok(res)
     }
+
+    fn entry(&mut self, key: K) -> Result<MapEntry<'_, K, V>> {
+        if !self.value.value.contains_key(&key) {
+            if let Some(Operation::Update(v)) = map_utils::get_inner_operation(self, &key)? {
+                self.value.load(key.clone(), v)?;
+            }
+        }
+
+        Ok(MapEntry::new(key, &mut self.value))
+    }
 }
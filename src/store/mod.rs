@@ -2,7 +2,7 @@ mod value;
 pub use value::ValueStore;
 
 mod map;
-pub use map::MapStore;
+pub use map::{MapEntry, MapStore};
 
 mod tx;
 
@@ -11,7 +11,20 @@ mod utils;
 mod vec;
 pub use vec::VecStore;
 
+mod set;
+pub use set::SetStore;
+
+mod deque;
+pub use deque::DequeStore;
+
+mod counter;
+pub use counter::CounterStore;
+
+mod index;
+pub use index::Index;
+
 mod doublekey_map;
+#[cfg(feature = "json")]
 mod tree;
 
 pub use doublekey_map::DoubleKeyMapStore;
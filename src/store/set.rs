@@ -0,0 +1,72 @@
+use core::fmt::Debug;
+
+use alloc::collections::btree_set;
+
+use crate::{merkle::Merkle, model::Set, Operation, Result, SnapshotableStorage, Store};
+
+use super::utils::set_utils;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+/// Defining the basic behavior of the set application layer
+pub trait SetStore<K>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+{
+    fn contains(&self, key: &K) -> Result<bool>;
+
+    /// Return true if key isn't in set before.
+    fn insert(&mut self, key: K) -> Result<bool>;
+
+    /// Return true if key is in set before.
+    fn remove(&mut self, key: &K) -> Result<bool>;
+
+    /// Iterate members in key order.
+    fn iter(&self) -> Result<btree_set::IntoIter<K>>;
+
+    /// Count members, this scans every version of the namespace like `iter`.
+    fn len(&self) -> Result<usize>;
+
+    /// Scans the namespace, see `len`.
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// Implementing the middle and cache layers is the behavior of set
+impl<S, M, K> SetStore<K> for SnapshotableStorage<S, M, Set<K>>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    S: Store,
+    M: Merkle,
+{
+    fn contains(&self, key: &K) -> Result<bool> {
+        match self.value.value.get(key) {
+            Some(Operation::Update(())) => Ok(true),
+            Some(Operation::Delete) => Ok(false),
+            None => set_utils::contains_inner(self, key),
+        }
+    }
+
+    fn insert(&mut self, key: K) -> Result<bool> {
+        let res = !self.contains(&key)?;
+        self.value.value.insert(key, Operation::Update(()));
+        Ok(res)
+    }
+
+    fn remove(&mut self, key: &K) -> Result<bool> {
+        let res = self.contains(key)?;
+        self.value.value.insert(key.clone(), Operation::Delete);
+        Ok(res)
+    }
+
+    fn iter(&self) -> Result<btree_set::IntoIter<K>> {
+        let mut members = set_utils::inner_members(self)?;
+        set_utils::apply_cache(&mut members, &self.value);
+        Ok(members.into_iter())
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.iter()?.len())
+    }
+}
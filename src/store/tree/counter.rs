@@ -0,0 +1,20 @@
+use crate::prelude::Tree;
+use alloc::vec::Vec;
+
+use crate::{
+    merkle::Merkle,
+    model::{Counter, CounterValue},
+    CounterStore, Result, SnapshotableStorage, Store,
+};
+
+impl<S, M, N> Tree for SnapshotableStorage<S, M, Counter<N>>
+where
+    N: CounterValue,
+    S: Store,
+    M: Merkle,
+{
+    fn tree_get(&self, _key: &[u8]) -> Result<Vec<u8>> {
+        let value = self.get()?;
+        Ok(serde_json::to_vec(&value)?)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::prelude::Tree;
+use alloc::vec::Vec;
+
+use crate::{merkle::Merkle, model::Deque, DequeStore, Result, SnapshotableStorage, Store};
+
+use core::fmt::Debug;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+impl<S, M, T> Tree for SnapshotableStorage<S, M, Deque<T>>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+    S: Store,
+    M: Merkle,
+{
+    /// Key is the position from front.
+    fn tree_get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let position: u64 = serde_json::from_slice::<u64>(key)?;
+
+        if position >= self.len()? {
+            return Ok(Vec::new());
+        }
+
+        let value = self.get_index(self.head()? + position as i64)?;
+        if let Some(val) = value {
+            let bytes = serde_json::to_vec(val.as_ref())?;
+            Ok(bytes)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::prelude::Tree;
+use alloc::vec::Vec;
+
+use crate::{merkle::Merkle, model::Set, Result, SetStore, SnapshotableStorage, Store};
+
+use core::fmt::Debug;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+impl<S, M, K> Tree for SnapshotableStorage<S, M, Set<K>>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    S: Store,
+    M: Merkle,
+{
+    fn tree_get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let key: K = serde_json::from_slice::<K>(key)?;
+
+        if self.contains(&key)? {
+            let bytes = serde_json::to_vec(&true)?;
+            Ok(bytes)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
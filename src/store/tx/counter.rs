@@ -0,0 +1,26 @@
+use crate::merkle::Merkle;
+use crate::model::{Counter, CounterValue};
+use crate::{CounterStore, Result, Store, Transaction};
+
+impl<'a, S, M, N> CounterStore<N> for Transaction<'a, S, M, Counter<N>>
+where
+    N: CounterValue,
+    S: Store,
+    M: Merkle,
+{
+    fn get(&self) -> Result<N> {
+        let base = self.store.get()?;
+        self.charge_read(&[], Some(&base), true)?;
+        self.value.apply(base)
+    }
+
+    fn add(&mut self, n: N) -> Result<()> {
+        self.charge_write(&[], Some(&n))?;
+        self.value.add(n)
+    }
+
+    fn sub(&mut self, n: N) -> Result<()> {
+        self.charge_write(&[], Some(&n))?;
+        self.value.sub(n)
+    }
+}
@@ -0,0 +1,111 @@
+use crate::merkle::Merkle;
+use crate::model::Deque;
+use crate::{Cow, DequeStore, Operation, OrderedKey, Result, Store, Transaction};
+use alloc::vec::{self, Vec};
+use core::fmt::Debug;
+use serde::{Deserialize, Serialize};
+
+impl<'a, S, M, T> Transaction<'a, S, M, Deque<T>>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+    S: Store,
+    M: Merkle,
+{
+    fn head(&self) -> Result<i64> {
+        match self.value.head {
+            Some(head) => Ok(head),
+            None => self.store.head(),
+        }
+    }
+
+    fn tail(&self) -> Result<i64> {
+        match self.value.tail {
+            Some(tail) => Ok(tail),
+            None => self.store.tail(),
+        }
+    }
+
+    fn get_index(&self, index: i64) -> Result<Option<Cow<'_, T>>> {
+        match self.value.value.get(&index) {
+            Some(Operation::Update(v)) => Ok(Some(Cow::Borrowed(v))),
+            Some(Operation::Delete) => Ok(None),
+            None => {
+                let lookup = !self.store.value.value.contains_key(&index);
+                let value = self.store.get_index(index)?;
+                self.charge_read(&index.to_key_bytes()?, value.as_deref(), lookup)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+impl<'a, S, M, T> DequeStore<T> for Transaction<'a, S, M, Deque<T>>
+where
+    T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+    S: Store,
+    M: Merkle,
+{
+    fn push_back(&mut self, value: T) -> Result<()> {
+        let tail = self.tail()?;
+        self.charge_write(&tail.to_key_bytes()?, Some(&value))?;
+        self.value.value.insert(tail, Operation::Update(value));
+        self.value.tail = Some(tail + 1);
+        Ok(())
+    }
+
+    fn push_front(&mut self, value: T) -> Result<()> {
+        let head = self.head()? - 1;
+        self.charge_write(&head.to_key_bytes()?, Some(&value))?;
+        self.value.value.insert(head, Operation::Update(value));
+        self.value.head = Some(head);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Result<Option<T>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        let head = self.head()?;
+        let res = self.get_index(head)?.map(|v| v.clone());
+        self.charge_write::<T>(&head.to_key_bytes()?, None)?;
+        self.value.value.insert(head, Operation::Delete);
+        self.value.head = Some(head + 1);
+        Ok(res)
+    }
+
+    fn pop_back(&mut self) -> Result<Option<T>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        let tail = self.tail()? - 1;
+        let res = self.get_index(tail)?.map(|v| v.clone());
+        self.charge_write::<T>(&tail.to_key_bytes()?, None)?;
+        self.value.value.insert(tail, Operation::Delete);
+        self.value.tail = Some(tail);
+        Ok(res)
+    }
+
+    fn front(&self) -> Result<Option<Cow<'_, T>>> {
+        if self.is_empty()? {
+            return Ok(None);
+        }
+
+        self.get_index(self.head()?)
+    }
+
+    fn len(&self) -> Result<u64> {
+        Ok((self.tail()? - self.head()?) as u64)
+    }
+
+    fn iter(&self) -> Result<vec::IntoIter<T>> {
+        let mut values = Vec::new();
+        for index in self.head()?..self.tail()? {
+            if let Some(v) = self.get_index(index)? {
+                values.push(v.clone());
+            }
+        }
+        Ok(values.into_iter())
+    }
+}
@@ -1,8 +1,9 @@
 use crate::merkle::Merkle;
 use crate::model::DoubleKeyMap;
-use crate::{Cow, DoubleKeyMapStore, Operation, Result, Store, Transaction};
+use crate::{Cow, DoubleKeyMapStore, Operation, OrderedKey, Result, Store, Transaction};
 
 use crate::store::utils::doublekeymap_utils;
+use alloc::collections::btree_map;
 use core::fmt::Debug;
 #[cfg(feature = "cbor")]
 use serde::{Deserialize, Serialize};
@@ -26,7 +27,10 @@ where
             None => {
                 let lower_value = self.store.value.value.value.get(key);
                 match lower_value {
-                    Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
+                    Some(Operation::Update(v)) => {
+                        self.charge_read(&key.to_key_bytes()?, Some(v), false)?;
+                        Some(Cow::Borrowed(v))
+                    }
                     Some(Operation::Delete) => None,
                     None => None,
                 }
@@ -41,8 +45,16 @@ where
         }
 
         if !self.value.value.value.contains_key(key) {
-            if let Some(operation) = doublekeymap_utils::get_inner_operation(self.store, key)? {
-                self.value.value.value.insert(key.clone(), operation);
+            let key_bytes = key.to_key_bytes()?;
+            let lower_value = doublekeymap_utils::get_inner_value(self.store, key)?;
+            self.charge_read(&key_bytes, lower_value.as_ref(), true)?;
+            if let Some(value) = lower_value {
+                // Changes through the reference are charged as a write of the value read.
+                self.charge_write(&key_bytes, Some(&value))?;
+                self.value
+                    .value
+                    .value
+                    .insert(key.clone(), Operation::Update(value));
             } else {
                 return Ok(None);
             }
@@ -57,6 +69,7 @@ where
 
     fn insert(&mut self, key1: K1, key2: K2, value: V) -> Result<Option<V>> {
         let key = (key1, key2);
+        self.charge_write(&key.to_key_bytes()?, Some(&value))?;
         let operation = Operation::Update(value);
         let mut pre_val = None;
         if let Some(operation) = self.value.value.value.get_mut(&key) {
@@ -73,13 +86,18 @@ where
 
     fn remove(&mut self, key1: &K1, key2: &K2) -> Result<Option<V>> {
         let key = &(key1.clone(), key2.clone());
+        let key_bytes = key.to_key_bytes()?;
+        self.charge_write::<V>(&key_bytes, None)?;
         let res = if let Some(op) = self.value.value.value.remove(key) {
             match op {
                 Operation::Update(v) => Some(v),
                 Operation::Delete => None,
             }
         } else {
-            self.store.get(key1, key2)?.map(|v| v.clone())
+            let lookup = !self.store.value.value.value.contains_key(key);
+            let value = self.store.get(key1, key2)?.map(|v| v.clone());
+            self.charge_read(&key_bytes, value.as_ref(), lookup)?;
+            value
         };
 
         self.value
@@ -89,4 +107,31 @@ where
 
         Ok(res)
     }
+
+    fn iter_prefix(&self, key1: &K1) -> Result<btree_map::IntoIter<K2, V>> {
+        // One scan of the backend, entries are charged as reads.
+        let prefix = doublekeymap_utils::key_prefix(key1)?;
+        self.charge_read::<V>(&prefix, None, true)?;
+        let mut entries: btree_map::BTreeMap<K2, V> = self.store.iter_prefix(key1)?.collect();
+        for (key2, value) in entries.iter() {
+            let key_bytes = (key1.clone(), key2.clone()).to_key_bytes()?;
+            self.charge_read(&key_bytes, Some(value), false)?;
+        }
+        doublekeymap_utils::apply_prefix_cache(&mut entries, &self.value, key1);
+        Ok(entries.into_iter())
+    }
+
+    fn remove_prefix(&mut self, key1: &K1) -> Result<usize> {
+        let mut count = 0;
+        for (key2, _) in self.iter_prefix(key1)? {
+            let key_bytes = (key1.clone(), key2.clone()).to_key_bytes()?;
+            self.charge_write::<V>(&key_bytes, None)?;
+            self.value
+                .value
+                .value
+                .insert((key1.clone(), key2), Operation::Delete);
+            count += 1;
+        }
+        Ok(count)
+    }
 }
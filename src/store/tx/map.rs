@@ -1,6 +1,7 @@
 use crate::merkle::Merkle;
 use crate::model::Map;
-use crate::{Cow, MapStore, Operation, Store, Transaction};
+use crate::store::utils::map_utils;
+use crate::{Cow, MapEntry, MapStore, Operation, OrderedKey, Store, Transaction};
 
 use core::fmt::Debug;
 #[cfg(feature = "cbor")]
@@ -14,15 +15,24 @@ where
     M: Merkle,
 {
     fn get(&self, key: &K) -> crate::Result<Option<Cow<'_, V>>> {
-        let self_value = self.value.value.get(key);
-        Ok(match self_value {
-            Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
+        if let Some(operation) = self.value.value.get(key) {
+            return Ok(match operation {
+                Operation::Update(v) => Some(Cow::Borrowed(v)),
+                Operation::Delete => None,
+            });
+        }
+        let key_bytes = key.to_key_bytes()?;
+        Ok(match self.store.value.value.get(key) {
+            Some(Operation::Update(v)) => {
+                self.charge_read(&key_bytes, Some(v), false)?;
+                Some(Cow::Borrowed(v))
+            }
             Some(Operation::Delete) => None,
-            None => match self.store.value.value.get(key) {
-                Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
-                Some(Operation::Delete) => None,
-                None => self.store.get(key)?,
-            },
+            None => {
+                let value = map_utils::get_inner_value(self.store, key)?;
+                self.charge_read(&key_bytes, value.as_ref(), true)?;
+                value.map(Cow::Owned)
+            }
         })
     }
 
@@ -32,12 +42,11 @@ where
         }
 
         if !self.value.value.contains_key(key) {
-            let lower_value = self.store.get(key)?;
-            if let Some(v) = lower_value {
-                let value = v.clone();
-                self.value
-                    .value
-                    .insert(key.clone(), Operation::Update(value));
+            let lower_value = self.get(key)?.map(|v| v.clone());
+            if let Some(value) = lower_value {
+                // Changes through the reference are charged as a write of the value read.
+                self.charge_write(&key.to_key_bytes()?, Some(&value))?;
+                self.value.load(key.clone(), value)?;
             } else {
                 return Ok(None);
             }
@@ -53,6 +62,7 @@ where
     }
 
     fn insert(&mut self, key: K, value: V) -> crate::Result<Option<V>> {
+        self.charge_write(&key.to_key_bytes()?, Some(&value))?;
         let operation = Operation::Update(value);
         let mut pre_val = None;
         if let Some(operation) = self.value.value.get_mut(&key) {
@@ -63,22 +73,52 @@ where
                 Operation::Delete => {}
             }
         }
+        self.value.expire.remove(&key);
+        self.value.loaded.remove(&key);
         self.value.value.insert(key, operation);
         Ok(pre_val)
     }
 
+    fn insert_with_ttl(
+        &mut self,
+        key: K,
+        value: V,
+        expire_height: i64,
+    ) -> crate::Result<Option<V>> {
+        map_utils::check_expire_height(expire_height, self.store.height)?;
+        let res = self.insert(key.clone(), value)?;
+        self.value.expire.insert(key, expire_height);
+        Ok(res)
+    }
+
     fn remove(&mut self, key: &K) -> crate::Result<Option<V>> {
+        self.charge_write::<V>(&key.to_key_bytes()?, None)?;
+        self.value.expire.remove(key);
+        self.value.loaded.remove(key);
         let res = if let Some(op) = self.value.value.remove(key) {
             match op {
                 Operation::Update(v) => Some(v),
                 Operation::Delete => None,
             }
         } else {
-            self.store.get(key)?.map(|v| v.clone())
+            self.get(key)?.map(|v| v.clone())
         };
 
         self.value.value.insert(key.clone(), Operation::Delete);
 
         Ok(res)
     }
+
+    fn entry(&mut self, key: K) -> crate::Result<MapEntry<'_, K, V>> {
+        if !self.value.value.contains_key(&key) {
+            let lower_value = self.get(&key)?.map(|v| v.clone());
+            // The entry may write, it is charged as a write of the value read.
+            self.charge_write(&key.to_key_bytes()?, lower_value.as_ref())?;
+            if let Some(value) = lower_value {
+                self.value.load(key.clone(), value)?;
+            }
+        }
+
+        Ok(MapEntry::new(key, &mut self.value))
+    }
 }
@@ -1,4 +1,7 @@
+mod counter;
+mod deque;
 mod doublekey_map;
 mod map;
+mod set;
 mod value;
 mod vec;
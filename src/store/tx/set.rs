@@ -0,0 +1,58 @@
+use crate::merkle::Merkle;
+use crate::model::Set;
+use crate::store::utils::set_utils;
+use crate::{Operation, OrderedKey, Result, SetStore, Store, Transaction};
+
+use alloc::collections::btree_set;
+use core::fmt::Debug;
+#[cfg(feature = "cbor")]
+use serde::{Deserialize, Serialize};
+
+impl<'a, S, M, K> SetStore<K> for Transaction<'a, S, M, Set<K>>
+where
+    K: Clone + PartialEq + Eq + Serialize + for<'de> Deserialize<'de> + Ord + PartialOrd + Debug,
+    S: Store,
+    M: Merkle,
+{
+    fn contains(&self, key: &K) -> Result<bool> {
+        match self.value.value.get(key) {
+            Some(Operation::Update(())) => Ok(true),
+            Some(Operation::Delete) => Ok(false),
+            None => {
+                let lookup = !self.store.value.value.contains_key(key);
+                let res = self.store.contains(key)?;
+                self.charge_read(&key.to_key_bytes()?, res.then_some(&()), lookup)?;
+                Ok(res)
+            }
+        }
+    }
+
+    fn insert(&mut self, key: K) -> Result<bool> {
+        self.charge_write(&key.to_key_bytes()?, Some(&()))?;
+        let res = !self.contains(&key)?;
+        self.value.value.insert(key, Operation::Update(()));
+        Ok(res)
+    }
+
+    fn remove(&mut self, key: &K) -> Result<bool> {
+        self.charge_write::<()>(&key.to_key_bytes()?, None)?;
+        let res = self.contains(key)?;
+        self.value.value.insert(key.clone(), Operation::Delete);
+        Ok(res)
+    }
+
+    fn iter(&self) -> Result<btree_set::IntoIter<K>> {
+        // One scan of the backend, members are charged as reads.
+        self.charge_read::<()>(&[], None, true)?;
+        let mut members: btree_set::BTreeSet<K> = self.store.iter()?.collect();
+        for member in members.iter() {
+            self.charge_read(&member.to_key_bytes()?, Some(&()), false)?;
+        }
+        set_utils::apply_cache(&mut members, &self.value);
+        Ok(members.into_iter())
+    }
+
+    fn len(&self) -> Result<usize> {
+        Ok(self.iter()?.len())
+    }
+}
@@ -2,6 +2,7 @@ use core::fmt::Debug;
 
 use crate::merkle::Merkle;
 use crate::model::Value;
+use crate::store::utils::value_utils;
 use crate::{Cow, Operation, Result, Store, Transaction, ValueStore};
 use serde::{Deserialize, Serialize};
 
@@ -16,15 +17,23 @@ where
             Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
             Some(Operation::Delete) => None,
             None => match &self.store.value.value {
-                Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
+                Some(Operation::Update(v)) => {
+                    self.charge_read(&[], Some(v), false)?;
+                    Some(Cow::Borrowed(v))
+                }
                 Some(Operation::Delete) => None,
-                None => self.store.get()?,
+                None => {
+                    let value = value_utils::get_inner_value(self.store)?;
+                    self.charge_read(&[], value.as_ref(), true)?;
+                    value.map(Cow::Owned)
+                }
             },
         })
     }
 
     fn set(&mut self, value: T) -> Result<Option<T>> {
-        return if let Some(operation) = self.value.value.as_ref() {
+        self.charge_write(&[], Some(&value))?;
+        if let Some(operation) = self.value.value.as_ref() {
             match operation {
                 Operation::Update(v) => {
                     let v2 = v.clone();
@@ -36,11 +45,12 @@ where
         } else {
             self.value.value = Some(Operation::Update(value));
             Ok(None)
-        };
+        }
     }
 
     fn del(&mut self) -> Result<Option<T>> {
-        return if let Some(operation) = self.value.value.as_ref() {
+        self.charge_write::<T>(&[], None)?;
+        if let Some(operation) = self.value.value.as_ref() {
             match operation {
                 Operation::Update(v) => {
                     let v2 = v.clone();
@@ -51,6 +61,6 @@ where
             }
         } else {
             Ok(None)
-        };
+        }
     }
 }
@@ -3,7 +3,7 @@ use core::fmt::Debug;
 use crate::merkle::Merkle;
 use crate::model::Vec;
 use crate::store::utils::vec_utils;
-use crate::{Cow, Operation, Result, Store, Transaction, VecStore};
+use crate::{Cow, Operation, OrderedKey, Result, Store, Transaction, VecStore};
 use serde::{Deserialize, Serialize};
 
 impl<'a, S, M, T> VecStore<T> for Transaction<'a, S, M, Vec<T>>
@@ -13,16 +13,24 @@ where
     M: Merkle,
 {
     fn get(&self, index: u64) -> crate::Result<Option<Cow<'_, T>>> {
-        let self_value = self.value.value.get(&index);
-
-        Ok(match self_value {
-            Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
+        if let Some(operation) = self.value.value.get(&index) {
+            return Ok(match operation {
+                Operation::Update(v) => Some(Cow::Borrowed(v)),
+                Operation::Delete => None,
+            });
+        }
+        let key_bytes = index.to_key_bytes()?;
+        Ok(match self.store.value.value.get(&index) {
+            Some(Operation::Update(v)) => {
+                self.charge_read(&key_bytes, Some(v), false)?;
+                Some(Cow::Borrowed(v))
+            }
             Some(Operation::Delete) => None,
-            None => match self.store.value.value.get(&index) {
-                Some(Operation::Update(v)) => Some(Cow::Borrowed(v)),
-                Some(Operation::Delete) => None,
-                None => self.store.get(index)?,
-            },
+            None => {
+                let value = vec_utils::get_inner_value(self.store, index)?;
+                self.charge_read(&key_bytes, value.as_ref(), true)?;
+                value.map(Cow::Owned)
+            }
         })
     }
 
@@ -30,9 +38,12 @@ where
         if let Some(Operation::Delete) = self.value.value.get(&index) {
             return Ok(None);
         }
-        if let alloc::collections::btree_map::Entry::Vacant(e) = self.value.value.entry(index) {
-            if let Some(operation) = vec_utils::get_inner_operation(self.store, index)? {
-                e.insert(operation);
+        if !self.value.value.contains_key(&index) {
+            let lower_value = self.get(index)?.map(|v| v.clone());
+            if let Some(value) = lower_value {
+                // Changes through the reference are charged as a write of the value read.
+                self.charge_write(&index.to_key_bytes()?, Some(&value))?;
+                self.value.value.insert(index, Operation::Update(value));
             } else {
                 return Ok(None);
             }
@@ -48,8 +59,9 @@ where
     }
 
     fn insert(&mut self, value: T) -> Result<Option<T>> {
-        let operation = Operation::Update(value);
         let index = self.value.value.len() as u64;
+        self.charge_write(&index.to_key_bytes()?, Some(&value))?;
+        let operation = Operation::Update(value);
         let mut pre_val = None;
         if let Some(operation) = self.value.value.get_mut(&index) {
             match operation {
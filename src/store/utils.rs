@@ -1,14 +1,12 @@
 //!
+//! Read helpers for the application layer
 //!
 
-use alloc::vec::Vec as alloc_vec;
 use core::fmt::Debug;
 
-use crate::snapshot::{FromStoreBytes, StoreValue};
-use crate::utils::cbor_encode;
 use crate::{
     model::{DoubleKeyMap, Map, Value, Vec},
-    Operation, Result, SnapshotableStorage, Store,
+    Operation, OrderedKey, Result, SnapshotableStorage, Store,
 };
 #[cfg(feature = "cbor")]
 use serde::{Deserialize, Serialize};
@@ -35,18 +33,8 @@ pub(crate) mod map_utils {
         S: Store,
         M: Merkle,
     {
-        let key_bytes = cbor_encode(key)?;
-        let store_key = vss.storage_tuple_key(&key_bytes);
-        let bytes = vss.store.get_ge2((&store_key.0, &store_key.1))?;
-        // let store_key = vss.storage_key(&key_bytes);
-        // let bytes = vss.store.get_ge(&*store_key)?;
-        if let Some(bytes) = bytes {
-            let value = StoreValue::from_bytes(&bytes)?;
-            let operation = Operation::from_bytes(&value.operation)?;
-            Ok(Some(operation))
-        } else {
-            Ok(None)
-        }
+        let key_bytes = key.to_key_bytes()?;
+        vss.get_latest_operation(&key_bytes)
     }
 
     pub fn get_inner_value<S, M, K, V>(
@@ -76,9 +64,22 @@ pub(crate) mod map_utils {
             Ok(None)
         }
     }
+
+    /// Expire height must be visible at the height built on `height`.
+    pub fn check_expire_height(expire_height: i64, height: i64) -> Result<()> {
+        if expire_height <= height {
+            return Err(crate::Error::ExpireHeightPassed {
+                expire_height,
+                current: height,
+            });
+        }
+        Ok(())
+    }
 }
 
 pub(crate) mod doublekeymap_utils {
+    use alloc::{collections::BTreeMap, vec::Vec};
+
     use crate::merkle::Merkle;
 
     use super::*;
@@ -108,18 +109,8 @@ pub(crate) mod doublekeymap_utils {
         S: Store,
         M: Merkle,
     {
-        let key_bytes = cbor_encode(key)?;
-        let store_key = vss.storage_tuple_key(&key_bytes);
-        let bytes = vss.store.get_ge2((&store_key.0, &store_key.1))?;
-        // let store_key = vss.storage_key(&key_bytes);
-        // let bytes = vss.store.get_ge(&*store_key)?;
-        if let Some(bytes) = bytes {
-            let value = StoreValue::from_bytes(&bytes)?;
-            let operation = Operation::from_bytes(&value.operation)?;
-            Ok(Some(operation))
-        } else {
-            Ok(None)
-        }
+        let key_bytes = key.to_key_bytes()?;
+        vss.get_latest_operation(&key_bytes)
     }
 
     pub fn get_inner_value<S, M, K1, K2, V>(
@@ -157,6 +148,87 @@ pub(crate) mod doublekeymap_utils {
             Ok(None)
         }
     }
+
+    /// Key prefix of all entries under key1.
+    ///
+    /// Pair is encoded as the concatenation of both keys, so entries under one
+    /// key1 are contiguous in backend and sorted by key2.
+    pub fn key_prefix<K1: OrderedKey>(key1: &K1) -> Result<Vec<u8>> {
+        key1.to_key_bytes()
+    }
+
+    pub fn inner_prefix_entries<S, M, K1, K2, V>(
+        vss: &SnapshotableStorage<S, M, DoubleKeyMap<K1, K2, V>>,
+        key1: &K1,
+    ) -> Result<BTreeMap<K2, V>>
+    where
+        K1: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        K2: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+        S: Store,
+        M: Merkle,
+    {
+        let mut entries = BTreeMap::new();
+        let prefix = key_prefix(key1)?;
+        for (key_bytes, operation) in vss.scan_prefix_operations(&prefix)? {
+            let operation =
+                Operation::from_bytes(&operation).map_err(|e| e.with_key(&key_bytes))?;
+            if let Operation::Update(v) = operation {
+                let (_, key2) =
+                    <(K1, K2)>::from_key_bytes(&key_bytes).map_err(|e| e.with_key(&key_bytes))?;
+                entries.insert(key2, v);
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn apply_prefix_cache<K1, K2, V>(
+        entries: &mut BTreeMap<K2, V>,
+        cache: &DoubleKeyMap<K1, K2, V>,
+        key1: &K1,
+    ) where
+        K1: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        K2: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        V: Clone + Serialize + for<'de> Deserialize<'de> + Debug,
+    {
+        for ((k1, k2), operation) in cache.value.value.iter() {
+            if k1 != key1 {
+                continue;
+            }
+            match operation {
+                Operation::Update(v) => entries.insert(k2.clone(), v.clone()),
+                Operation::Delete => entries.remove(k2),
+            };
+        }
+    }
 }
 
 pub(crate) mod vec_utils {
@@ -193,53 +265,174 @@ pub(crate) mod vec_utils {
         S: Store,
         M: Merkle,
     {
-        let key_bytes = cbor_encode(key)?;
-        let store_key = vss.storage_tuple_key(&key_bytes);
-        let bytes = vss.store.get_ge2((&store_key.0, &store_key.1))?;
-        if let Some(bytes) = bytes {
-            let value = StoreValue::from_bytes(&bytes)?;
-            let operation = Operation::from_bytes(&value.operation)?;
-            Ok(Some(operation))
-        } else {
-            Ok(None)
+        let key_bytes = key.to_key_bytes()?;
+        vss.get_latest_operation(&key_bytes)
+    }
+}
+
+pub(crate) mod set_utils {
+    use alloc::collections::BTreeSet;
+
+    use crate::{merkle::Merkle, model::Set, OperationBytes};
+
+    use super::*;
+
+    pub fn contains_inner<S, M, K>(vss: &SnapshotableStorage<S, M, Set<K>>, key: &K) -> Result<bool>
+    where
+        K: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        S: Store,
+        M: Merkle,
+    {
+        let key_bytes = key.to_key_bytes()?;
+        Ok(matches!(
+            vss.get_latest_operation_bytes(&key_bytes)?,
+            Some(OperationBytes::Update(_))
+        ))
+    }
+
+    pub fn inner_members<S, M, K>(vss: &SnapshotableStorage<S, M, Set<K>>) -> Result<BTreeSet<K>>
+    where
+        K: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+        S: Store,
+        M: Merkle,
+    {
+        let mut members = BTreeSet::new();
+        for (key_bytes, operation) in vss.scan_operations()? {
+            if let OperationBytes::Update(_) = operation {
+                let key = K::from_key_bytes(&key_bytes).map_err(|e| e.with_key(&key_bytes))?;
+                members.insert(key);
+            }
+        }
+        Ok(members)
+    }
+
+    pub fn apply_cache<K>(members: &mut BTreeSet<K>, cache: &Set<K>)
+    where
+        K: Clone
+            + PartialEq
+            + Eq
+            + Serialize
+            + for<'de> Deserialize<'de>
+            + Ord
+            + PartialOrd
+            + Debug,
+    {
+        for (key, operation) in cache.value.iter() {
+            match operation {
+                Operation::Update(()) => members.insert(key.clone()),
+                Operation::Delete => members.remove(key),
+            };
         }
     }
 }
 
-pub(crate) mod value_utils {
-    use crate::merkle::Merkle;
+pub(crate) mod deque_utils {
+    use crate::{
+        merkle::Merkle,
+        model::{deque, Deque},
+    };
 
     use super::*;
 
-    pub fn storage_key<S, M, T>(
-        vss: &SnapshotableStorage<S, M, Value<T>>,
-    ) -> (alloc_vec<u8>, alloc_vec<u8>)
+    pub fn get_inner_value<S, M, T>(
+        vss: &SnapshotableStorage<S, M, Deque<T>>,
+        index: i64,
+    ) -> Result<Option<T>>
+    where
+        T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+        S: Store,
+        M: Merkle,
+    {
+        let key_bytes = index.to_key_bytes()?;
+        match vss.get_latest_operation(&key_bytes)? {
+            Some(Operation::Update(v)) => Ok(Some(v)),
+            Some(Operation::Delete) | None => Ok(None),
+        }
+    }
+
+    fn get_inner_counter<S, M, T>(
+        vss: &SnapshotableStorage<S, M, Deque<T>>,
+        name: &[u8],
+    ) -> Result<i64>
+    where
+        T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+        S: Store,
+        M: Merkle,
+    {
+        match vss.get_latest_operation(name)? {
+            Some(Operation::Update(v)) => Ok(v),
+            Some(Operation::Delete) | None => Ok(0),
+        }
+    }
+
+    pub fn get_inner_head<S, M, T>(vss: &SnapshotableStorage<S, M, Deque<T>>) -> Result<i64>
     where
         T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
         S: Store,
         M: Merkle,
     {
-        let inner_key = alloc_vec::new();
-        vss.storage_tuple_key(&inner_key)
+        get_inner_counter(vss, deque::HEAD_KEY)
     }
 
+    pub fn get_inner_tail<S, M, T>(vss: &SnapshotableStorage<S, M, Deque<T>>) -> Result<i64>
+    where
+        T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
+        S: Store,
+        M: Merkle,
+    {
+        get_inner_counter(vss, deque::TAIL_KEY)
+    }
+}
+
+pub(crate) mod counter_utils {
+    use crate::{
+        merkle::Merkle,
+        model::{Counter, CounterValue},
+    };
+
+    use super::*;
+
+    pub fn get_inner_value<S, M, N>(vss: &SnapshotableStorage<S, M, Counter<N>>) -> Result<N>
+    where
+        N: CounterValue,
+        S: Store,
+        M: Merkle,
+    {
+        match vss.get_latest_operation(&[])? {
+            Some(Operation::Update(v)) => Ok(v),
+            Some(Operation::Delete) | None => Ok(N::default()),
+        }
+    }
+}
+
+pub(crate) mod value_utils {
+    use crate::merkle::Merkle;
+
+    use super::*;
+
     pub fn get_inner_value<S, M, T>(vss: &SnapshotableStorage<S, M, Value<T>>) -> Result<Option<T>>
     where
         T: Clone + Debug + Serialize + for<'de> Deserialize<'de>,
         S: Store,
         M: Merkle,
     {
-        let store_key = storage_key(vss);
-        match vss.store.get_ge2((&store_key.0, &store_key.1))? {
-            Some(bytes) => {
-                let value = StoreValue::from_bytes(&bytes)?;
-                let operation = Operation::from_bytes(&value.operation)?;
-                match operation {
-                    Operation::Update(v) => Ok(Some(v)),
-                    Operation::Delete => Ok(None),
-                }
-            }
-            None => Ok(None),
+        match vss.get_latest_operation(&[])? {
+            Some(Operation::Update(v)) => Ok(Some(v)),
+            Some(Operation::Delete) | None => Ok(None),
         }
     }
 }
@@ -41,7 +41,7 @@ where
             return Ok(None);
         }
 
-        if self.value.value.get(&index).is_none() {
+        if !self.value.value.contains_key(&index) {
             if let Some(operation) = vec_utils::get_inner_operation(self, index)? {
                 self.value.value.insert(index, operation);
             } else {
@@ -5,15 +5,19 @@ mod cbor {
     use crate::{Error, Result};
     use alloc::string::ToString;
     use alloc::vec::Vec;
-    use ciborium::ser::into_writer;
-    use serde::Serialize;
+    use ciborium::{de::from_reader, ser::into_writer};
+    use serde::{de::DeserializeOwned, Serialize};
 
     pub fn cbor_encode(t: impl Serialize) -> Result<Vec<u8>> {
         let mut value = Vec::new();
         into_writer(&t, &mut value).map_err(|e| Error::CborSerIoError(e.to_string()))?;
         Ok(value)
     }
+
+    pub fn cbor_decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        from_reader(bytes).map_err(|e| Error::CborDeIoError(e.to_string()))
+    }
 }
 
 #[cfg(feature = "cbor")]
-pub use cbor::cbor_encode;
+pub use cbor::{cbor_decode, cbor_encode};
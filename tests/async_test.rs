@@ -0,0 +1,118 @@
+use bs3::backend::{BlockingStore, MemoryBackend};
+use bs3::merkle::append_only::AppendOnlyMerkle;
+use bs3::model::{Map, Value};
+use bs3::{AsyncStorage, AsyncStore, MapStore, Result, SnapshotableStorage, ValueStore};
+use sha3::Sha3_256;
+use tokio::runtime::{Builder, Runtime};
+
+fn runtime() -> Runtime {
+    Builder::new_multi_thread().build().unwrap()
+}
+
+#[test]
+fn blocking_store_test() -> Result<()> {
+    runtime().block_on(async {
+        let mut store = BlockingStore::new(MemoryBackend::new());
+        store
+            .execute(vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"c".to_vec(), b"3".to_vec()),
+            ])
+            .await?;
+
+        // Clones share the store.
+        let reader = store.clone();
+        assert_eq!(reader.get(b"b").await?, Some(b"2".to_vec()));
+        assert_eq!(reader.get(b"d").await?, None);
+        let records = reader.range(b"a", b"b").await?;
+        assert_eq!(
+            records,
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ]
+        );
+
+        store.remove(vec![b"a".to_vec(), b"d".to_vec()]).await?;
+        assert_eq!(reader.get(b"a").await?, None);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn async_store_storage_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    runtime().block_on(async {
+        let store = BlockingStore::new(MemoryBackend::new());
+        let storage =
+            AsyncStorage::<_, M, _>::open(Map::default(), "map".to_string(), store.clone()).await?;
+        storage.write(|s| s.insert(1u32, 10u32)).await?;
+        assert_eq!(storage.commit().await?, 1);
+        let root = storage.read(|s| Ok(s.root()?.to_vec())).await?;
+
+        // The records are in the async store.
+        let reopened =
+            AsyncStorage::<_, M, _>::open(Map::<u32, u32>::default(), "map".to_string(), store)
+                .await?;
+        assert_eq!(reopened.height().await?, 1);
+        assert_eq!(reopened.get(1).await?, Some(10));
+        assert_eq!(reopened.read(|s| Ok(s.root()?.to_vec())).await?, root);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn async_storage_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    runtime().block_on(async {
+        let ss =
+            SnapshotableStorage::<_, M, _>::new(Map::<u32, u32>::default(), MemoryBackend::new())?;
+        let storage = AsyncStorage::new(ss);
+
+        storage
+            .write(|s| {
+                for i in 0..10 {
+                    s.insert(i, i)?;
+                }
+                Ok(())
+            })
+            .await?;
+        assert_eq!(storage.commit().await?, 1);
+
+        let readers: std::vec::Vec<_> = (0..10)
+            .map(|i| {
+                let storage = storage.clone();
+                tokio::spawn(async move { storage.get(i).await })
+            })
+            .collect();
+        storage.write(|s| s.insert(0, 100)).await?;
+        assert_eq!(storage.commit().await?, 2);
+        for (i, reader) in readers.into_iter().enumerate() {
+            let value = reader.await.unwrap()?.unwrap();
+            assert!(value == i as u32 || (i == 0 && value == 100));
+        }
+
+        assert_eq!(storage.get(0).await?, Some(100));
+        assert_eq!(storage.get(10).await?, None);
+        storage.rollback(1).await?;
+        assert_eq!(storage.height().await?, 1);
+        assert_eq!(storage.get(0).await?, Some(0));
+        let root = storage.read(|s| Ok(s.root()?.to_vec())).await?;
+        assert_eq!(root.len(), 32);
+
+        let ss =
+            SnapshotableStorage::<_, M, _>::new(Value::<u32>::default(), MemoryBackend::new())?;
+        let value = AsyncStorage::new(ss);
+        value.write(|s| s.set(7)).await?;
+        assert_eq!(value.get().await?, Some(7));
+        value.commit().await?;
+        assert_eq!(value.get().await?, Some(7));
+
+        Ok(())
+    })
+}
@@ -0,0 +1,136 @@
+use bs3::backend::MemoryBackend;
+use bs3::merkle::empty::EmptyMerkle;
+use bs3::model::{registry, Map, Model};
+use bs3::{Error, Operation, OperationBytes, Result, SnapshotableStorage, Store, Transaction};
+use sha3::Sha3_512;
+
+const TALLY: u32 = registry::USER_CODE_START + 1;
+
+/// A user defined model, keeps the last written count.
+#[derive(Debug, Clone, Default)]
+struct Tally {
+    value: Option<u64>,
+}
+
+impl Model for Tally {
+    fn operations(&mut self) -> Result<Vec<(Vec<u8>, OperationBytes)>> {
+        Ok(match self.value.take() {
+            Some(v) => vec![(Vec::new(), Operation::Update(v).to_bytes()?)],
+            None => Vec::new(),
+        })
+    }
+
+    fn type_code(&self) -> u32 {
+        TALLY
+    }
+
+    fn merge(&mut self, other: Self) {
+        if other.value.is_some() {
+            self.value = other.value;
+        }
+    }
+}
+
+trait TallyStore {
+    fn get(&self) -> Result<u64>;
+
+    fn incr(&mut self, by: u64) -> Result<u64>;
+}
+
+impl<S: Store, M: bs3::merkle::Merkle> TallyStore for SnapshotableStorage<S, M, Tally> {
+    fn get(&self) -> Result<u64> {
+        if let Some(v) = self.cache().value {
+            return Ok(v);
+        }
+        match self.get_latest_operation(&[])? {
+            Some(Operation::Update(v)) => Ok(v),
+            Some(Operation::Delete) | None => Ok(0),
+        }
+    }
+
+    fn incr(&mut self, by: u64) -> Result<u64> {
+        let v = TallyStore::get(self)? + by;
+        self.cache_mut().value = Some(v);
+        Ok(v)
+    }
+}
+
+impl<S: Store, M: bs3::merkle::Merkle> TallyStore for Transaction<'_, S, M, Tally> {
+    fn get(&self) -> Result<u64> {
+        match self.value.value {
+            Some(v) => Ok(v),
+            None => TallyStore::get(self.store),
+        }
+    }
+
+    fn incr(&mut self, by: u64) -> Result<u64> {
+        let v = TallyStore::get(self)? + by;
+        self.value.value = Some(v);
+        Ok(v)
+    }
+}
+
+#[test]
+fn custom_model_test() -> Result<()> {
+    registry::register(TALLY, "tally")?;
+    registry::register(TALLY, "tally")?;
+    assert_eq!(registry::type_name(TALLY), Some("tally"));
+    assert_eq!(Tally::default().type_name(), "tally");
+
+    let e = registry::register(TALLY, "other").err().unwrap();
+    assert!(matches!(
+        e,
+        Error::TypeCodeConflict {
+            code: TALLY,
+            name: "tally"
+        }
+    ));
+    let e = registry::register(registry::MAP, "my_map").err().unwrap();
+    assert_eq!(e.to_string(), "type code 3 is already used by map");
+    assert!(registry::register(7, "my_model").is_err());
+    let e = registry::register(TALLY + 1, "tally").err().unwrap();
+    assert_eq!(e.to_string(), "type name tally is already used by code 257");
+    let e = registry::register(TALLY + 1, "counter").err().unwrap();
+    assert!(matches!(
+        e,
+        Error::TypeNameConflict {
+            name: "counter",
+            code: registry::COUNTER
+        }
+    ));
+
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(
+        Tally::default(),
+        MemoryBackend::new(),
+    )?;
+    assert_eq!(ss.incr(2)?, 2);
+    assert_eq!(ss.commit()?, 1);
+    assert_eq!(TallyStore::get(&ss)?, 2);
+
+    let mut tx = ss.transaction();
+    assert_eq!(tx.incr(3)?, 5);
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(TallyStore::get(&ss)?, 5);
+    assert_eq!(TallyStore::get(&ss.at(1)?)?, 2);
+
+    let reloaded = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(
+        Tally::default(),
+        ss.store().clone(),
+    )?;
+    assert_eq!(TallyStore::get(&reloaded)?, 5);
+
+    let e = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(
+        Map::<u32, u32>::default(),
+        ss.store().clone(),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "store type mismatch, expected map(3) but found tally(257)"
+    );
+
+    Ok(())
+}
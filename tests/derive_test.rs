@@ -0,0 +1,187 @@
+use bs3::backend::MemoryBackend;
+use bs3::merkle::append_only::AppendOnlyMerkle;
+use bs3::model::{DoubleKeyMap, Map, Value, Vec};
+use bs3::{
+    import_snapshot, CommitMetadata, Cow, DoubleKeyMapStore, Error, Forkable, MapStore, Operation,
+    Result, State, ValueStore, VecStore,
+};
+use sha3::Sha3_256;
+use std::sync::{Arc, Mutex};
+
+#[derive(State)]
+pub struct Bank {
+    pub balances: Map<String, u64>,
+    pub allowances: DoubleKeyMap<String, String, u64>,
+    pub history: Vec<u64>,
+    pub total: Value<u64>,
+}
+
+type Storage = BankStorage<MemoryBackend, AppendOnlyMerkle<Sha3_256>>;
+
+#[test]
+fn state_derive_test() -> Result<()> {
+    let mut bank = Storage::new(MemoryBackend::new())?;
+    assert_eq!(bank.balances.namespace(), "Bank.balances");
+
+    let mut tx = bank.transaction();
+    tx.balances.insert("alice".to_string(), 10)?;
+    tx.allowances
+        .insert("alice".to_string(), "bob".to_string(), 5)?;
+    tx.history.insert(10)?;
+    tx.total.set(10)?;
+
+    let mut fork = tx.clone();
+    fork.balances.insert("bob".to_string(), 1)?;
+    tx.merge(fork.cache());
+
+    let cache = tx.cache();
+    bank.execute(cache);
+    assert_eq!(bank.commit()?, 1);
+    let root = bank.root()?;
+
+    assert_eq!(bank.balances.get(&"bob".to_string())?, Some(Cow::Owned(1)));
+    assert_eq!(
+        bank.allowances
+            .get(&"alice".to_string(), &"bob".to_string())?,
+        Some(Cow::Owned(5))
+    );
+    assert_eq!(bank.history.get(0)?, Some(Cow::Owned(10)));
+    assert_eq!(bank.total.get()?, Some(Cow::Owned(10)));
+
+    bank.total.set(20)?;
+    assert_eq!(bank.commit()?, 2);
+    assert_ne!(bank.root()?, root);
+
+    bank.rollback(1)?;
+    assert_eq!(bank.height(), 1);
+    assert_eq!(bank.root()?, root);
+    assert_eq!(bank.total.get()?, Some(Cow::Owned(10)));
+
+    let named = Storage::new_with_name("bank", MemoryBackend::new())?;
+    assert_eq!(named.total.namespace(), "bank.total");
+
+    bank.balances.insert("carol".to_string(), 3)?;
+    assert_eq!(bank.commit()?, 2);
+    let snapshot = bank.export_snapshot(1)?;
+    assert_eq!(snapshot.manifest.namespaces.len(), 4);
+    assert_eq!(snapshot.manifest.root, root.to_vec());
+
+    let mut s = MemoryBackend::new();
+    import_snapshot::<_, AppendOnlyMerkle<Sha3_256>, _>(
+        &mut s,
+        &snapshot.manifest,
+        &snapshot.chunks,
+    )?;
+    let imported = Storage::new(s)?;
+    assert_eq!(imported.height(), 1);
+    assert_eq!(imported.root()?, root);
+    assert_eq!(imported.balances.get(&"carol".to_string())?, None);
+    assert_eq!(imported.total.get()?, Some(Cow::Owned(10)));
+
+    let mut backup = MemoryBackend::new();
+    bank.backup_to(&mut backup, 1)?;
+    bank.backup_incremental_to(&mut backup, 1, 2)?;
+    let restored = Storage::restore(&backup, MemoryBackend::new(), &bank.root()?)?;
+    assert_eq!(restored.height(), 2);
+    assert_eq!(
+        restored.balances.get(&"carol".to_string())?,
+        Some(Cow::Owned(3))
+    );
+    assert!(Storage::restore(&backup, MemoryBackend::new(), &root).is_err());
+
+    let heights = Arc::new(Mutex::new(std::vec::Vec::new()));
+    let sink = heights.clone();
+    let mut hooked = Storage::new(MemoryBackend::new())?
+        .with_commit_validator(|_, _, changes| {
+            // Total must follow history.
+            if changes.history.len() != changes.total.len() {
+                return Err(Error::CommitRejected("total not updated".to_string()));
+            }
+            Ok(())
+        })
+        .with_commit_listener(move |height, root, changes| {
+            sink.lock()
+                .unwrap()
+                .push((height, root.to_vec(), changes.total.clone()));
+        });
+    hooked.history.insert(5)?;
+    assert!(matches!(hooked.commit(), Err(Error::CommitRejected(_))));
+    assert_eq!(hooked.height(), 0);
+    assert_eq!(hooked.history.pending_operations()?.len(), 1);
+    hooked.total.set(5)?;
+    let metadata = CommitMetadata {
+        app_version: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(hooked.commit_with_metadata(metadata.clone())?, 1);
+    assert_eq!(
+        hooked.history.commit_info(1)?.unwrap().metadata,
+        Some(metadata)
+    );
+    assert_eq!(
+        *heights.lock().unwrap(),
+        vec![(1, hooked.root()?.to_vec(), vec![((), Operation::Update(5))])]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn state_commit_atomic_test() -> Result<()> {
+    let mut bank = Storage::new(MemoryBackend::new())?;
+    bank.total =
+        bank.total
+            .with_commit_validator(|pending| match pending.operations()?.as_slice() {
+                [(_, bs3::Operation::Update(0))] => {
+                    Err(Error::CommitRejected("zero total".to_string()))
+                }
+                _ => Ok(()),
+            });
+
+    // Listeners of a field run once every field is written.
+    let notified = Arc::new(Mutex::new(std::vec::Vec::new()));
+    let sink = notified.clone();
+    bank.balances = bank.balances.with_commit_listener(move |event| {
+        sink.lock().unwrap().push(event.operations().unwrap());
+    });
+
+    bank.balances.insert("alice".to_string(), 10)?;
+    bank.history.insert(10)?;
+    bank.total.set(0)?;
+    // Total fails after balances and history are staged.
+    assert!(matches!(bank.commit(), Err(Error::CommitRejected(_))));
+    assert_eq!(bank.height(), 0);
+    assert_eq!(bank.balances.height, 0);
+    assert_eq!(bank.history.height, 0);
+    assert_eq!(bank.balances.pending_operations()?.len(), 1);
+
+    bank.total.set(10)?;
+    assert_eq!(bank.commit()?, 1);
+    assert_eq!(bank.history.height, 1);
+    assert_eq!(
+        *notified.lock().unwrap(),
+        vec![vec![("alice".to_string(), Operation::Update(10))]]
+    );
+    assert_eq!(
+        bank.balances.get(&"alice".to_string())?,
+        Some(Cow::Owned(10))
+    );
+
+    // Each field holds its own copy of a memory backend, the one of total only has
+    // its records.
+    let e = Storage::new(bank.total.store().clone()).err().unwrap();
+    assert!(matches!(
+        e,
+        Error::StateHeightMismatch {
+            expected: 0,
+            found: 1,
+            ..
+        }
+    ));
+    assert_eq!(
+        e.to_string(),
+        "storage Bank.total is at height 1, expected 0"
+    );
+
+    Ok(())
+}
@@ -1,9 +1,16 @@
 use bs3::backend::MemoryBackend;
+use bs3::merkle::append_only::AppendOnlyMerkle;
 use bs3::merkle::empty::EmptyMerkle;
-use bs3::model::{DoubleKeyMap, Map, Value, Vec};
-use bs3::{Cow, DoubleKeyMapStore, MapStore, Result, ValueStore, VecStore};
-use bs3::{SnapshotableStorage, Transaction};
-use sha3::Sha3_512;
+use bs3::model::{Counter, Deque, DoubleKeyMap, Map, Set, Value, Vec};
+use bs3::{
+    import_snapshot, merkle_key, verify, CommitMetadata, Forkable, ProblemKind, SnapshotWriter,
+    SnapshotableStorage, Transaction,
+};
+use bs3::{
+    Access, CounterStore, Cow, DequeStore, DoubleKeyMapStore, Error, Index, MapStore, Operation,
+    OrderedKey, Result, SetStore, TxMetrics, ValueStore, VecStore,
+};
+use sha3::{Sha3_256, Sha3_512};
 
 fn map_mem_test() -> Result<()> {
     let m = Map::default();
@@ -137,6 +144,1260 @@ fn tx_vec_mem_test() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn map_latest_index_mem_test() -> Result<()> {
+    let m = Map::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?.with_latest_index();
+
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 10)?;
+    assert_eq!(ss.commit()?, 2);
+    ss.remove(&2)?;
+    assert_eq!(ss.commit()?, 3);
+
+    assert!(ss.store().cache.keys().any(|k| k.starts_with(b"-lw-")));
+
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(10)));
+    assert_eq!(ss.get(&2)?, None);
+    assert_eq!(ss.get(&3)?, None);
+
+    ss.rollback(2)?;
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(10)));
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(2)));
+
+    ss.rollback(1)?;
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(2)));
+
+    ss.rollback(0)?;
+    assert_eq!(ss.get(&1)?, None);
+
+    // Versions above the rollback target are not read after commits again.
+    assert_eq!(ss.commit()?, 1);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.get(&1)?, None);
+    assert_eq!(ss.get(&2)?, None);
+    ss.insert(3, 3)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, None);
+    assert_eq!(ss.get(&3)?, Some(Cow::Owned(3)));
+
+    Ok(())
+}
+
+#[test]
+fn error_mem_test() -> Result<()> {
+    let m = Map::<i32, i32>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?;
+    assert_eq!(ss.commit()?, 1);
+
+    let e = ss.rollback(2).unwrap_err();
+    assert!(matches!(
+        e,
+        Error::HeightOutOfRange {
+            requested: 2,
+            current: 1
+        }
+    ));
+    assert_eq!(e.to_string(), "height 2 out of range, current height is 1");
+
+    let v = Value::<i32>::default();
+    let e = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(v, ss.store().clone())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        e,
+        Error::TypeMissMatch {
+            expected: 1,
+            expected_name: "value",
+            found: 3,
+            found_name: "map",
+        }
+    ));
+    assert_eq!(
+        e.to_string(),
+        "store type mismatch, expected value(1) but found map(3)"
+    );
+
+    // Type record of a namespace written with cbor keys, without a layout.
+    let mut store = ss.store().clone();
+    store
+        .cache
+        .insert(b"-ty".to_vec(), vec![0xa1, 0x62, b't', b'y', 3]);
+    let e =
+        SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::<i32, i32>::default(), store)
+            .err()
+            .unwrap();
+    assert!(matches!(
+        e,
+        Error::LayoutMismatch {
+            expected: 1,
+            found: 0,
+            ..
+        }
+    ));
+    assert_eq!(e.to_string(), "namespace  uses key layout 0, expected 1");
+
+    Ok(())
+}
+
+#[test]
+fn migrate_mem_test() -> Result<()> {
+    let m = Map::<u32, u32>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, s)?;
+    ss.insert(1, 1)?;
+    ss.insert(300, 300)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 2)?;
+    ss.remove(&300)?;
+    assert_eq!(ss.commit()?, 2);
+    let root = ss.root()?;
+
+    // The namespace as written by 0.1: cbor keys, no layout and no commit records.
+    let mut legacy = MemoryBackend::new();
+    for (key, bytes) in ss.store().cache.iter() {
+        let text = String::from_utf8_lossy(key);
+        if let Some(version) = text.strip_prefix("-kw-") {
+            let (hex_key, height) = version.rsplit_once('-').unwrap();
+            let k = u32::from_key_bytes(&hex::decode(hex_key).unwrap())?;
+            let mut cbor = std::vec::Vec::new();
+            ciborium::ser::into_writer(&k, &mut cbor).unwrap();
+            let key = format!("-kw-{}-{}", hex::encode(cbor), height);
+            legacy.cache.insert(key.into_bytes(), bytes.clone());
+        } else if text == "-ch" || text.starts_with("-mr-") {
+            legacy.cache.insert(key.clone(), bytes.clone());
+        }
+    }
+    legacy
+        .cache
+        .insert(b"-ty".to_vec(), vec![0xa1, 0x62, b't', b'y', 3]);
+
+    let m = Map::<u32, u32>::default();
+    assert!(matches!(
+        SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, legacy.clone()),
+        Err(Error::LayoutMismatch { found: 0, .. })
+    ));
+
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::migrate_key_layout(
+        m,
+        String::new(),
+        &legacy,
+        MemoryBackend::new(),
+    )?;
+    assert_eq!(ss.height, 2);
+    assert_eq!(ss.root()?, root);
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(2)));
+    assert_eq!(ss.get(&300)?, None);
+    assert_eq!(ss.at(1)?.get(&300)?, Some(Cow::Owned(300)));
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(2)));
+
+    // Namespaces are migrated once, into an empty target.
+    let m = Map::<u32, u32>::default();
+    assert!(matches!(
+        SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::migrate_key_layout(
+            m,
+            String::new(),
+            ss.store(),
+            MemoryBackend::new(),
+        ),
+        Err(Error::LayoutMismatch { found: 1, .. })
+    ));
+    let m = Map::<u32, u32>::default();
+    assert!(matches!(
+        SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::migrate_key_layout(
+            m,
+            String::new(),
+            &legacy,
+            ss.store().clone(),
+        ),
+        Err(Error::StoreNotEmpty { .. })
+    ));
+
+    // Keys of values don't depend on the layout.
+    let v = Value::<i32>::default();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(v, MemoryBackend::new())?;
+    ss.set(7)?;
+    assert_eq!(ss.commit()?, 1);
+    let mut store = ss.store().clone();
+    store
+        .cache
+        .insert(b"-ty".to_vec(), vec![0xa1, 0x62, b't', b'y', 1]);
+    let ss =
+        SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Value::<i32>::default(), store)?;
+    assert_eq!(ss.get()?, Some(Cow::Owned(7)));
+
+    Ok(())
+}
+
+#[test]
+fn set_mem_test() -> Result<()> {
+    let m = Set::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, s)?;
+
+    assert!(ss.insert(3)?);
+    assert!(ss.insert(1)?);
+    assert!(!ss.insert(1)?);
+    assert!(ss.insert(2)?);
+    assert!(ss.remove(&2)?);
+    assert!(!ss.remove(&4)?);
+    assert_eq!(ss.commit()?, 1);
+    let root = ss.root()?;
+
+    assert!(ss.contains(&1)?);
+    assert!(!ss.contains(&2)?);
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![1, 3]);
+
+    assert!(ss.insert(5)?);
+    assert!(ss.remove(&1)?);
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![3, 5]);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.len()?, 2);
+    assert_ne!(ss.root()?, root);
+
+    let mut old = ss.at(1)?;
+    assert!(old.contains(&1)?);
+    assert!(!old.contains(&5)?);
+    assert_eq!(old.iter()?.collect::<std::vec::Vec<_>>(), vec![1, 3]);
+    assert_eq!(old.root()?, root);
+    assert!(ss.at(3).is_err());
+
+    // Views don't write.
+    old.insert(7)?;
+    assert!(matches!(
+        old.commit(),
+        Err(Error::ReadOnlyView { height: 1 })
+    ));
+    assert!(matches!(old.rollback(0), Err(Error::ReadOnlyView { .. })));
+    assert_eq!(ss.height, 2);
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![3, 5]);
+
+    Ok(())
+}
+
+#[test]
+fn tx_set_mem_test() -> Result<()> {
+    let m = Set::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?;
+    assert!(ss.insert(1)?);
+    assert_eq!(ss.commit()?, 1);
+    assert!(ss.insert(2)?);
+
+    let mut tx = Transaction::new(&ss);
+    assert!(tx.contains(&1)?);
+    assert!(tx.contains(&2)?);
+    assert!(tx.insert(3)?);
+    assert!(tx.remove(&1)?);
+    assert_eq!(tx.iter()?.collect::<std::vec::Vec<_>>(), vec![2, 3]);
+
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn deque_mem_test() -> Result<()> {
+    let d = Deque::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(d, s)?;
+
+    assert_eq!(ss.pop_front()?, None);
+    ss.push_back(2)?;
+    ss.push_back(3)?;
+    ss.push_front(1)?;
+    assert_eq!(ss.len()?, 3);
+    assert_eq!(ss.commit()?, 1);
+
+    assert_eq!(ss.front()?, Some(Cow::Owned(1)));
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(ss.pop_front()?, Some(1));
+    assert_eq!(ss.pop_back()?, Some(3));
+    ss.push_back(4)?;
+    assert_eq!(ss.commit()?, 2);
+
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![2, 4]);
+    assert_eq!(
+        ss.at(1)?.iter()?.collect::<std::vec::Vec<_>>(),
+        vec![1, 2, 3]
+    );
+
+    assert_eq!(ss.pop_front()?, Some(2));
+    assert_eq!(ss.pop_front()?, Some(4));
+    assert_eq!(ss.pop_front()?, None);
+    assert_eq!(ss.pop_back()?, None);
+    assert_eq!(ss.commit()?, 3);
+    assert!(ss.is_empty()?);
+    assert_eq!(ss.front()?, None);
+
+    Ok(())
+}
+
+#[test]
+fn tx_deque_mem_test() -> Result<()> {
+    let d = Deque::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(d, s)?;
+    ss.push_back(1)?;
+    ss.push_back(2)?;
+    assert_eq!(ss.commit()?, 1);
+
+    let mut tx = Transaction::new(&ss);
+    assert_eq!(tx.pop_front()?, Some(1));
+    tx.push_back(3)?;
+    tx.push_front(0)?;
+    assert_eq!(tx.front()?, Some(Cow::Borrowed(&0)));
+    assert_eq!(tx.len()?, 3);
+    assert_eq!(tx.iter()?.collect::<std::vec::Vec<_>>(), vec![0, 2, 3]);
+
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.iter()?.collect::<std::vec::Vec<_>>(), vec![0, 2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn doublekeymap_prefix_mem_test() -> Result<()> {
+    let m = DoubleKeyMap::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?;
+
+    ss.insert(1, 30, 130)?;
+    ss.insert(1, 2, 102)?;
+    ss.insert(10, 1, 1001)?;
+    ss.insert(2, 1, 201)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 25, 125)?;
+    ss.remove(&1, &2)?;
+
+    let entries = ss.iter_prefix(&1)?.collect::<std::vec::Vec<_>>();
+    assert_eq!(entries, vec![(25, 125), (30, 130)]);
+    assert_eq!(ss.commit()?, 2);
+    let entries = ss.iter_prefix(&1)?.collect::<std::vec::Vec<_>>();
+    assert_eq!(entries, vec![(25, 125), (30, 130)]);
+
+    let mut tx = Transaction::new(&ss);
+    tx.insert(1, 40, 140)?;
+    assert_eq!(tx.iter_prefix(&1)?.len(), 3);
+    assert_eq!(tx.remove_prefix(&1)?, 3);
+    assert_eq!(tx.iter_prefix(&1)?.len(), 0);
+    assert_eq!(tx.iter_prefix(&10)?.len(), 1);
+
+    assert_eq!(ss.remove_prefix(&1)?, 2);
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.iter_prefix(&1)?.len(), 0);
+    assert_eq!(ss.get(&2, &1)?, Some(Cow::Owned(201)));
+    assert_eq!(ss.at(1)?.iter_prefix(&1)?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn map_entry_mem_test() -> Result<()> {
+    let m = Map::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?;
+
+    *ss.entry(1)?.or_insert(10) += 1;
+    assert_eq!(*ss.entry(2)?.or_default(), 0);
+    assert_eq!(ss.commit()?, 1);
+
+    ss.entry(1)?.and_modify(|v| *v *= 2).or_insert(0);
+    ss.entry(3)?.and_modify(|v| *v *= 2).or_insert_with(|| 30);
+    assert!(ss.entry(2)?.is_occupied());
+    assert_eq!(ss.entry(2)?.remove(), Some(0));
+    assert!(!ss.entry(2)?.is_occupied());
+    assert_eq!(ss.entry(4)?.remove(), None);
+    assert_eq!(ss.commit()?, 2);
+
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(22)));
+    assert_eq!(ss.get(&2)?, None);
+    assert_eq!(ss.get(&3)?, Some(Cow::Owned(30)));
+
+    let mut tx = Transaction::new(&ss);
+    *tx.entry(1)?.or_insert(0) += 1;
+    *tx.entry(2)?.or_insert(5) += 1;
+    assert_eq!(tx.entry(3)?.remove(), Some(30));
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(22)));
+
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(23)));
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(6)));
+    assert_eq!(ss.get(&3)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn counter_mem_test() -> Result<()> {
+    let c = Counter::<u64>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(c, s)?;
+
+    ss.add(10)?;
+    ss.sub(3)?;
+    assert_eq!(ss.get()?, 7);
+    assert_eq!(ss.commit()?, 1);
+    assert_eq!(ss.get()?, 7);
+
+    let mut tx1 = Transaction::new(&ss);
+    let mut tx2 = Transaction::new(&ss);
+    tx1.add(5)?;
+    tx2.add(2)?;
+    tx2.sub(1)?;
+    assert_eq!(tx1.get()?, 12);
+    assert_eq!(tx2.get()?, 8);
+
+    tx1.merge(tx2.cache());
+    assert_eq!(tx1.get()?, 13);
+    let cache = tx1.cache();
+    ss.execute(cache);
+    assert_eq!(ss.get()?, 13);
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.get()?, 13);
+    assert_eq!(ss.at(1)?.get()?, 7);
+
+    ss.sub(20)?;
+    assert!(matches!(ss.get(), Err(Error::Overflow)));
+    assert!(matches!(ss.commit(), Err(Error::Overflow)));
+    assert_eq!(ss.height, 2);
+    ss.add(10)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get()?, 3);
+
+    ss.add(u64::MAX)?;
+    assert!(matches!(ss.add(1), Err(Error::Overflow)));
+
+    Ok(())
+}
+
+#[test]
+fn map_index_mem_test() -> Result<()> {
+    // (owner, balance)
+    let by_owner = Index::new("owner", |v: &(String, u64)| v.0.clone());
+    let by_balance = Index::new("balance", |v: &(String, u64)| v.1);
+
+    let m = Map::<u32, (String, u64)>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, s)?
+        .with_index(&by_owner)
+        .with_index(&by_balance);
+
+    ss.insert(1, ("alice".to_string(), 10))?;
+    ss.insert(2, ("bob".to_string(), 300))?;
+    ss.insert(3, ("alice".to_string(), 20))?;
+    assert!(ss.get_by_index(&by_owner, &"alice".to_string())?.is_empty());
+    assert_eq!(ss.commit()?, 1);
+
+    let alice = ss.get_by_index(&by_owner, &"alice".to_string())?;
+    assert_eq!(
+        alice.keys().copied().collect::<std::vec::Vec<_>>(),
+        vec![1, 3]
+    );
+
+    ss.insert(3, ("bob".to_string(), 20))?;
+    ss.remove(&2)?;
+    ss.insert(4, ("carol".to_string(), 5))?;
+    assert_eq!(ss.commit()?, 2);
+
+    let alice = ss.get_by_index(&by_owner, &"alice".to_string())?;
+    assert_eq!(alice.keys().copied().collect::<std::vec::Vec<_>>(), vec![1]);
+    let bob = ss.get_by_index(&by_owner, &"bob".to_string())?;
+    assert_eq!(bob.get(&3), Some(&("bob".to_string(), 20)));
+    assert_eq!(bob.len(), 1);
+
+    let range = ss.range_by_index(&by_balance, 5..20)?;
+    assert_eq!(
+        range
+            .iter()
+            .map(|(b, k, _)| (*b, *k))
+            .collect::<std::vec::Vec<_>>(),
+        vec![(5, 4), (10, 1)]
+    );
+    let range = ss.range_by_index(&by_balance, 10..=20)?;
+    assert_eq!(range.len(), 2);
+    assert_eq!(ss.range_by_index(&by_balance, ..)?.len(), 3);
+    assert!(ss.range_by_index(&by_balance, 21..)?.is_empty());
+
+    let old = ss.at(1)?;
+    assert_eq!(old.get_by_index(&by_owner, &"alice".to_string())?.len(), 2);
+    assert_eq!(
+        old.range_by_index(&by_balance, 100..)?[0],
+        (300, 2, ("bob".to_string(), 300))
+    );
+
+    ss.rollback(1)?;
+    assert_eq!(ss.get_by_index(&by_owner, &"bob".to_string())?.len(), 1);
+    assert!(ss.get_by_index(&by_owner, &"carol".to_string())?.is_empty());
+
+    // Values committed before the index is attached.
+    let m = Map::<u32, (String, u64)>::default();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, MemoryBackend::new())?;
+    ss.insert(1, ("alice".to_string(), 10))?;
+    ss.insert(2, ("bob".to_string(), 300))?;
+    assert_eq!(ss.commit()?, 1);
+    let mut ss = ss.with_index(&by_owner);
+    ss.insert(3, ("alice".to_string(), 20))?;
+    assert_eq!(ss.commit()?, 2);
+    let alice = ss.get_by_index(&by_owner, &"alice".to_string())?;
+    assert_eq!(alice.keys().copied().collect::<std::vec::Vec<_>>(), vec![3]);
+
+    ss.rebuild_index(&by_owner)?;
+    let alice = ss.get_by_index(&by_owner, &"alice".to_string())?;
+    assert_eq!(
+        alice.keys().copied().collect::<std::vec::Vec<_>>(),
+        vec![1, 3]
+    );
+    assert_eq!(ss.get_by_index(&by_owner, &"bob".to_string())?.len(), 1);
+    assert!(ss
+        .at(1)?
+        .get_by_index(&by_owner, &"bob".to_string())?
+        .is_empty());
+
+    ss.insert(1, ("bob".to_string(), 10))?;
+    assert_eq!(ss.commit()?, 3);
+    let bob = ss.get_by_index(&by_owner, &"bob".to_string())?;
+    assert_eq!(
+        bob.keys().copied().collect::<std::vec::Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(ss.get_by_index(&by_owner, &"alice".to_string())?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn map_ttl_mem_test() -> Result<()> {
+    let m = Map::<u32, u32>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, s)?;
+
+    assert!(matches!(
+        ss.insert_with_ttl(1, 1, 0),
+        Err(Error::ExpireHeightPassed { .. })
+    ));
+
+    // Visible at heights 1 and 2.
+    ss.insert_with_ttl(1, 1, 2)?;
+    ss.insert_with_ttl(2, 2, 2)?;
+    ss.insert_with_ttl(3, 3, 2)?;
+    // Dropped before commit.
+    ss.insert(3, 3)?;
+    assert_eq!(ss.commit()?, 1);
+
+    let mut tx = ss.transaction();
+    tx.insert_with_ttl(4, 4, 2)?;
+    let cache = tx.value;
+    ss.execute(cache);
+    // Committed write after ttl cancels it.
+    ss.insert(2, 20)?;
+    assert_eq!(ss.commit()?, 2);
+    let root = ss.root()?;
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(ss.get(&4)?, Some(Cow::Owned(4)));
+
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, None);
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(20)));
+    assert_eq!(ss.get(&3)?, Some(Cow::Owned(3)));
+    assert_eq!(ss.get(&4)?, None);
+    assert_eq!(ss.at(2)?.get(&1)?, Some(Cow::Owned(1)));
+
+    ss.rollback(2)?;
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(ss.root()?, root);
+
+    // Expired again, unless written in the same commit.
+    ss.insert(4, 40)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, None);
+    assert_eq!(ss.get(&4)?, Some(Cow::Owned(40)));
+
+    // Entry writes drop the expiry like insert and remove.
+    ss.insert_with_ttl(5, 5, 4)?;
+    assert_eq!(ss.entry(5)?.remove(), Some(5));
+    *ss.entry(5)?.or_insert(50) += 1;
+    let mut tx = ss.transaction();
+    tx.insert_with_ttl(6, 6, 4)?;
+    tx.entry(6)?.remove();
+    tx.entry(6)?.or_insert(60);
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 4);
+    assert_eq!(ss.commit()?, 5);
+    assert_eq!(ss.get(&5)?, Some(Cow::Owned(51)));
+    assert_eq!(ss.get(&6)?, Some(Cow::Owned(60)));
+
+    // Reading a committed value through an entry doesn't write it.
+    ss.insert_with_ttl(7, 7, 6)?;
+    assert_eq!(ss.commit()?, 6);
+    assert!(ss.entry(7)?.is_occupied());
+    assert_eq!(*ss.entry(7)?.or_insert(70), 7);
+    assert_eq!(ss.get_mut(&7)?, Some(&mut 7));
+    let mut tx = ss.transaction();
+    assert_eq!(*tx.entry(7)?.or_insert(70), 7);
+    let cache = tx.value;
+    ss.execute(cache);
+    assert_eq!(ss.commit()?, 7);
+    assert_eq!(ss.get(&7)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn snapshot_sync_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let by_parity = Index::new("parity", |v: &u32| v % 2);
+    let m = Map::<u32, u32>::default();
+    let mut ss =
+        SnapshotableStorage::<_, M, _>::new_with_name(m, "sync".to_string(), MemoryBackend::new())?
+            .with_index(&by_parity);
+
+    for i in 0..50 {
+        ss.insert(i, i)?;
+    }
+    ss.insert_with_ttl(100, 100, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.remove(&0)?;
+    ss.insert(1, 11)?;
+    assert_eq!(ss.commit()?, 2);
+    let root = ss.root()?;
+    ss.insert(2, 22)?;
+    assert_eq!(ss.commit()?, 3);
+
+    let mut writer = SnapshotWriter::<Sha3_256>::with_chunk_size(2, 64);
+    writer.add(&ss)?;
+    // Streamed chunks are the ones held by the snapshot.
+    let streamed = writer.manifest()?;
+    let chunks = writer.chunks().collect::<Result<std::vec::Vec<_>>>()?;
+    let snapshot = writer.finish()?;
+    assert_eq!(streamed, snapshot.manifest);
+    assert_eq!(chunks, snapshot.chunks);
+    let manifest = &snapshot.manifest;
+    assert_eq!(manifest.height, 2);
+    assert_eq!(manifest.root, root.to_vec());
+    assert!(snapshot.chunks.len() > 1);
+    assert!(snapshot.chunks.iter().all(|c| c.len() <= 64));
+
+    let mut tampered = snapshot.chunks.clone();
+    tampered[1][0] ^= 1;
+    let mut s = MemoryBackend::new();
+    assert!(matches!(
+        import_snapshot::<_, M, _>(&mut s, manifest, &tampered),
+        Err(Error::InvalidSnapshot(_))
+    ));
+
+    let mut s = MemoryBackend::new();
+    import_snapshot::<_, M, _>(&mut s, manifest, &snapshot.chunks)?;
+    assert!(matches!(
+        import_snapshot::<_, M, _>(&mut s.clone(), manifest, &snapshot.chunks),
+        Err(Error::StoreNotEmpty { .. })
+    ));
+
+    let mut imported = SnapshotableStorage::<_, M, _>::new_with_name(
+        Map::<u32, u32>::default(),
+        "sync".to_string(),
+        s,
+    )?
+    .with_index(&by_parity);
+    assert_eq!(imported.height, 2);
+    assert_eq!(imported.root()?, root);
+    assert_eq!(imported.get(&0)?, None);
+    assert_eq!(imported.get(&1)?, Some(Cow::Owned(11)));
+    assert_eq!(imported.get(&2)?, Some(Cow::Owned(2)));
+    assert_eq!(imported.get_by_index(&by_parity, &1)?.len(), 25);
+
+    // Same next commit as the source.
+    imported.insert(2, 22)?;
+    assert_eq!(imported.commit()?, 3);
+    assert_eq!(imported.root()?, ss.root()?);
+    assert_eq!(imported.get(&100)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn backup_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, M, _>::new(m, MemoryBackend::new())?.with_latest_index();
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 10)?;
+    assert_eq!(ss.commit()?, 2);
+    let root = ss.root()?;
+    ss.remove(&2)?;
+    ss.insert(3, 3)?;
+    assert_eq!(ss.commit()?, 3);
+
+    let mut backup = MemoryBackend::new();
+    ss.at(2)?.backup_to(&mut backup, 2)?;
+    assert!(matches!(
+        ss.backup_to(&mut backup, 4),
+        Err(Error::HeightOutOfRange { .. })
+    ));
+
+    let copy = SnapshotableStorage::<_, M, _>::new(Map::<u32, u32>::default(), backup.clone())?
+        .with_latest_index();
+    assert_eq!(copy.height, 2);
+    assert_eq!(copy.root()?, root);
+    assert_eq!(copy.get(&1)?, Some(Cow::Owned(10)));
+    assert_eq!(copy.at(1)?.get(&1)?, Some(Cow::Owned(1)));
+
+    assert!(matches!(
+        ss.backup_incremental_to(&mut backup, 1, 3),
+        Err(Error::BackupHeightMismatch {
+            expected: 1,
+            found: 2
+        })
+    ));
+    ss.backup_incremental_to(&mut backup, 2, 3)?;
+
+    let restored = SnapshotableStorage::<_, M, _>::restore(
+        &backup,
+        Map::<u32, u32>::default(),
+        String::new(),
+        MemoryBackend::new(),
+        &ss.root()?,
+    )?
+    .with_latest_index();
+    assert_eq!(restored.height, 3);
+    assert_eq!(restored.get(&2)?, None);
+    assert_eq!(restored.get(&3)?, Some(Cow::Owned(3)));
+    assert_eq!(restored.get(&1)?, Some(Cow::Owned(10)));
+
+    assert!(matches!(
+        SnapshotableStorage::<_, M, _>::restore(
+            &backup,
+            Map::<u32, u32>::default(),
+            String::new(),
+            MemoryBackend::new(),
+            &root,
+        ),
+        Err(Error::BackupRootMismatch { height: 3 })
+    ));
+
+    // Only the records of the namespace are restored, not the ones of `foo-bar`.
+    let mut backup = MemoryBackend::new();
+    for name in ["foo", "foo-bar"] {
+        let mut s = SnapshotableStorage::<_, M, _>::new_with_name(
+            Map::<u32, u32>::default(),
+            name.to_string(),
+            MemoryBackend::new(),
+        )?;
+        s.insert(1, 1)?;
+        assert_eq!(s.commit()?, 1);
+        s.backup_to(&mut backup, 1)?;
+    }
+    let mut store = MemoryBackend::new();
+    bs3::restore_backup(&backup, &mut store, "foo")?;
+    assert!(store.cache.keys().all(|k| !k.starts_with(b"foo-bar")));
+    assert!(store.cache.contains_key(b"foo-ty".as_slice()));
+
+    Ok(())
+}
+
+#[test]
+fn commit_hooks_mem_test() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(std::vec::Vec::new()));
+    let sink = events.clone();
+
+    let m = Map::<u32, u32>::default();
+    let mut ss =
+        SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, MemoryBackend::new())?
+            .with_commit_validator(|pending| {
+                for (k, op) in pending.operations()? {
+                    if let Operation::Update(v) = op {
+                        if v < k {
+                            return Err(Error::CommitRejected(format!("{} below key", v)));
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .with_commit_listener(move |event| {
+                let operations = event.operations().unwrap();
+                sink.lock()
+                    .unwrap()
+                    .push((event.height, event.root.to_vec(), operations));
+            });
+
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+
+    ss.remove(&1)?;
+    ss.insert(3, 0)?;
+    assert_eq!(
+        ss.pending_operations()?,
+        vec![(1, Operation::Delete), (3, Operation::Update(0))]
+    );
+    assert!(matches!(ss.commit(), Err(Error::CommitRejected(_))));
+    assert_eq!(ss.height, 1);
+    // Vetoed cache is kept.
+    assert_eq!(ss.get(&1)?, None);
+    assert_eq!(ss.get(&3)?, Some(Cow::Borrowed(&0)));
+
+    ss.insert(3, 3)?;
+    assert_eq!(ss.commit()?, 2);
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, 1);
+    assert_eq!(
+        events[0].2,
+        vec![(1, Operation::Update(1)), (2, Operation::Update(2))]
+    );
+    assert_eq!(events[1].1, ss.root()?.to_vec());
+    assert_eq!(
+        events[1].2,
+        vec![(1, Operation::Delete), (3, Operation::Update(3))]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn commit_info_mem_test() -> Result<()> {
+    let m = Map::<u32, u32>::default();
+    let mut ss =
+        SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(m, MemoryBackend::new())?;
+
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    let metadata = CommitMetadata {
+        time: Some(1_650_000_000_000),
+        proposer: Some(b"validator-1".to_vec()),
+        app_version: Some(2),
+        extra: b"header".to_vec(),
+    };
+    ss.remove(&1)?;
+    assert_eq!(ss.commit_with_metadata(metadata.clone())?, 2);
+
+    let info = ss.commit_info(1)?.unwrap();
+    assert_eq!(info.keys, 2);
+    assert_eq!(info.metadata, None);
+    assert_eq!(info.root, ss.at(1)?.root()?);
+
+    let info = ss.commit_info(2)?.unwrap();
+    assert_eq!(info.height, 2);
+    assert_eq!(info.keys, 1);
+    assert_eq!(info.metadata, Some(metadata.clone()));
+    assert_eq!(info.root, ss.root()?);
+    assert!(matches!(
+        ss.commit_info(3),
+        Err(Error::HeightOutOfRange { .. })
+    ));
+
+    let mut backup = MemoryBackend::new();
+    ss.backup_to(&mut backup, 2)?;
+    let copy = SnapshotableStorage::<_, AppendOnlyMerkle<Sha3_256>, _>::new(
+        Map::<u32, u32>::default(),
+        backup,
+    )?;
+    assert_eq!(copy.commit_info(2)?.unwrap().metadata, Some(metadata));
+
+    Ok(())
+}
+
+#[test]
+fn tx_metrics_mem_test() -> Result<()> {
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(m, MemoryBackend::new())?;
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(3, 3)?;
+
+    let mut tx = ss.transaction();
+    assert_eq!(tx.metrics(), TxMetrics::default());
+    assert_eq!(tx.get(&1)?, Some(Cow::Owned(1)));
+    let read = tx.metrics();
+    assert_eq!(read.lookups, 1);
+    assert_eq!(read.keys_touched, 1);
+    assert!(read.bytes_read > 0);
+
+    // Uncommitted values of the storage are read without lookup.
+    assert_eq!(tx.get(&3)?, Some(Cow::Borrowed(&3)));
+    assert_eq!(tx.metrics().lookups, 1);
+    assert_eq!(tx.get(&9)?, None);
+    assert_eq!(tx.metrics().lookups, 2);
+
+    tx.insert(4, 4)?;
+    *tx.get_mut(&2)?.unwrap() += 1;
+    // Reads of the transaction cache are free.
+    assert_eq!(tx.get(&4)?, Some(Cow::Borrowed(&4)));
+    let metrics = tx.metrics();
+    assert_eq!(metrics.keys_touched, 5);
+    assert_eq!(metrics.lookups, 3);
+    assert!(metrics.bytes_written > 0);
+
+    // Accesses of a fork are counted by the transaction it's merged into.
+    let mut fork = tx.clone();
+    fork.insert(5, 5)?;
+    tx.merge(fork.cache());
+    assert_eq!(tx.metrics().keys_touched, 6);
+    assert!(tx.metrics().bytes_written > metrics.bytes_written);
+    let metrics = tx.metrics();
+
+    // Meter aborts writes once the budget is spent.
+    let budget = metrics.bytes_written * 2;
+    let mut tx = ss
+        .transaction()
+        .with_meter(move |access, metrics| match access {
+            Access::Write { bytes, .. } if metrics.bytes_written + bytes > budget => {
+                Err(Error::MeterExhausted("write budget".to_string()))
+            }
+            _ => Ok(()),
+        });
+    let mut written = 0;
+    let result = loop {
+        if let Err(e) = tx.insert(100 + written, written) {
+            break e;
+        }
+        written += 1;
+    };
+    assert!(matches!(result, Error::MeterExhausted(_)));
+    assert!(written > 0);
+    assert!(tx.metrics().bytes_written <= budget);
+    assert_eq!(tx.get(&(100 + written))?, None);
+
+    Ok(())
+}
+
+#[test]
+fn fork_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, M, _>::new(m, MemoryBackend::new())?;
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 10)?;
+    ss.insert(3, 3)?;
+    assert_eq!(ss.commit()?, 2);
+    ss.remove(&2)?;
+    assert_eq!(ss.commit()?, 3);
+
+    let mut fork = ss.fork("shadow", 1)?;
+    assert_eq!(fork.height, 1);
+    assert_eq!(fork.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(fork.get(&3)?, None);
+    assert_eq!(fork.root()?, ss.at(1)?.root()?);
+
+    fork.insert(4, 4)?;
+    fork.remove(&2)?;
+    assert_eq!(fork.commit()?, 2);
+    assert_eq!(fork.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(fork.get(&2)?, None);
+    assert_eq!(fork.get(&4)?, Some(Cow::Owned(4)));
+    assert_ne!(fork.root()?, ss.at(2)?.root()?);
+    assert_eq!(fork.at(1)?.get(&2)?, Some(Cow::Owned(2)));
+    assert_eq!(fork.at(1)?.root()?, ss.at(1)?.root()?);
+    assert_eq!(fork.commit_info(2)?.unwrap().keys, 2);
+
+    // Main line is untouched.
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(10)));
+    assert_eq!(ss.get(&4)?, None);
+    assert_eq!(ss.at_fork("shadow", 1)?.get(&1)?, Some(Cow::Owned(1)));
+    assert!(matches!(
+        ss.fork("shadow", 2),
+        Err(Error::ForkExists { .. })
+    ));
+    assert!(matches!(
+        fork.rollback(0),
+        Err(Error::HeightOutOfRange { .. })
+    ));
+
+    // Backup of a fork holds the versions of its ancestors.
+    let mut backup = MemoryBackend::new();
+    fork.backup_to(&mut backup, 2)?;
+    let copy = SnapshotableStorage::<_, M, _>::new_with_name(
+        Map::<u32, u32>::default(),
+        fork.namespace().to_string(),
+        backup,
+    )?;
+    assert_eq!(copy.root()?, fork.root()?);
+    assert_eq!(copy.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(copy.at(1)?.get(&2)?, Some(Cow::Owned(2)));
+
+    let mut deeper = fork.fork("deeper", 2)?;
+    assert_eq!(deeper.get(&4)?, Some(Cow::Owned(4)));
+    deeper.insert(1, 100)?;
+    assert_eq!(deeper.commit()?, 3);
+    assert_eq!(deeper.get(&1)?, Some(Cow::Owned(100)));
+    assert_eq!(deeper.at(2)?.root()?, fork.root()?);
+    assert_eq!(deeper.at(1)?.root()?, ss.at(1)?.root()?);
+
+    // Snapshot of a fork merges the latest versions of its lineage.
+    let snapshot = deeper.export_snapshot(3)?;
+    let mut s = MemoryBackend::new();
+    import_snapshot::<_, M, _>(&mut s, &snapshot.manifest, &snapshot.chunks)?;
+    let imported = SnapshotableStorage::<_, M, _>::new_with_name(
+        Map::<u32, u32>::default(),
+        deeper.namespace().to_string(),
+        s,
+    )?;
+    assert_eq!(imported.root()?, deeper.root()?);
+    assert_eq!(imported.get(&1)?, Some(Cow::Owned(100)));
+    assert_eq!(imported.get(&2)?, None);
+    assert_eq!(imported.get(&3)?, None);
+    assert_eq!(imported.get(&4)?, Some(Cow::Owned(4)));
+
+    // A fork created again after drop starts over.
+    ss.drop_fork("shadow")?;
+    assert!(matches!(
+        ss.open_fork("shadow"),
+        Err(Error::ForkNotFound { .. })
+    ));
+    let mut shadow = ss.fork("shadow", 2)?;
+    assert_eq!(shadow.get(&4)?, None);
+    assert_eq!(shadow.get(&3)?, Some(Cow::Owned(3)));
+    shadow.insert(5, 5)?;
+    assert_eq!(shadow.commit()?, 3);
+    let root = shadow.root()?;
+
+    ss.promote(shadow)?;
+    assert_eq!(ss.height, 3);
+    assert_eq!(ss.root()?, root);
+    assert_eq!(ss.get(&5)?, Some(Cow::Owned(5)));
+    // Removed on the main line above the fork height.
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(2)));
+
+    let reloaded =
+        SnapshotableStorage::<_, M, _>::new(Map::<u32, u32>::default(), ss.store().clone())?;
+    assert_eq!(reloaded.namespace(), ss.namespace());
+    assert_eq!(reloaded.root()?, root);
+
+    Ok(())
+}
+
+#[test]
+fn fork_rollback_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, M, _>::new(m, MemoryBackend::new())?.with_pruning();
+    for i in 1..=3 {
+        ss.insert(1, i)?;
+        assert_eq!(ss.commit()?, i as i64);
+    }
+    ss.fork("shadow", 2)?;
+
+    // The parent can't roll back below a live fork.
+    assert!(matches!(
+        ss.rollback(1),
+        Err(Error::ForkedAbove { height: 2, .. })
+    ));
+    assert_eq!(ss.height, 3);
+
+    // Commits of the parent above the fork height are not read by the fork.
+    ss.rollback(2)?;
+    ss.insert(1, 100)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(100)));
+    assert_eq!(ss.at_fork("shadow", 2)?.get(&1)?, Some(Cow::Owned(2)));
+
+    ss.drop_fork("shadow")?;
+    ss.rollback(1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 2);
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(1)));
+    assert_eq!(ss.get(&2)?, Some(Cow::Owned(2)));
+    let versions = ss
+        .store()
+        .cache
+        .keys()
+        .filter(|k| k.starts_with(b"-kw-"))
+        .count();
+    assert_eq!(versions, 2);
+
+    Ok(())
+}
+
+#[test]
+fn stats_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, M, _>::new(m, MemoryBackend::new())?;
+    ss.insert(1, 1)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+
+    // Maintained from a scan of the heights committed before.
+    let mut ss = ss.with_stats().with_pruning();
+    assert_eq!(ss.cached_stats()?, None);
+    ss.insert(1, 10)?;
+    ss.insert(3, 3)?;
+    ss.remove(&2)?;
+    assert_eq!(ss.commit()?, 2);
+    ss.remove(&3)?;
+    ss.remove(&4)?;
+    assert_eq!(ss.commit()?, 3);
+
+    let stats = ss.stats()?;
+    assert_eq!(stats.live_keys, 1);
+    assert_eq!(stats.versions, 7);
+    assert_eq!(stats.tombstones, 3);
+    assert_eq!(stats.oldest_height, Some(1));
+    assert_eq!(stats.newest_height, Some(3));
+    assert!(stats.key_bytes > 0 && stats.value_bytes > 0 && stats.merkle_bytes > 0);
+    assert_eq!(ss.cached_stats()?, Some(stats));
+
+    let stats = ss.at(2)?.stats()?;
+    assert_eq!(stats.live_keys, 2);
+    assert_eq!(stats.versions, 5);
+    assert_eq!(ss.at(2)?.cached_stats()?, Some(stats));
+
+    // Rollback removes the versions and statistics above the target height.
+    ss.rollback(2)?;
+    assert_eq!(ss.cached_stats()?, Some(ss.stats()?));
+    ss.insert(5, 5)?;
+    assert_eq!(ss.commit()?, 3);
+    let stats = ss.stats()?;
+    assert_eq!(stats.live_keys, 3);
+    assert_eq!(stats.versions, 6);
+    assert_eq!(ss.cached_stats()?, Some(stats));
+    assert_eq!(ss.get(&3)?, Some(Cow::Owned(3)));
+
+    // Heights committed without statistics are scanned again.
+    let m = Map::<u32, u32>::default();
+    let mut plain = SnapshotableStorage::<_, M, _>::new(m, ss.store().clone())?;
+    plain.insert(6, 6)?;
+    assert_eq!(plain.commit()?, 4);
+    let mut ss = plain.with_stats();
+    assert_eq!(ss.cached_stats()?, None);
+    ss.insert(7, 7)?;
+    assert_eq!(ss.commit()?, 5);
+    assert_eq!(ss.cached_stats()?, Some(ss.stats()?));
+
+    Ok(())
+}
+
+#[test]
+fn verify_mem_test() -> Result<()> {
+    type M = AppendOnlyMerkle<Sha3_256>;
+
+    let m = Map::<u32, u32>::default();
+    let s = MemoryBackend::new();
+    let mut ss = SnapshotableStorage::<_, M, _>::new_with_name(m, "main".into(), s)?
+        .with_latest_index()
+        .with_stats();
+    ss.insert_with_ttl(1, 1, 2)?;
+    ss.insert(2, 2)?;
+    assert_eq!(ss.commit()?, 1);
+    assert_eq!(ss.commit()?, 2);
+    // Expired key 1 is committed after key 9.
+    ss.insert(9, 9)?;
+    assert_eq!(ss.commit()?, 3);
+    assert_eq!(ss.get(&1)?, None);
+
+    let mut fork = ss.fork("shadow", 2)?;
+    fork.insert(3, 3)?;
+    assert_eq!(fork.commit()?, 3);
+
+    let report = verify::<_, M>(fork.store())?;
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(report.namespaces.len(), 2);
+    assert!(report.namespaces.iter().all(|n| n.height == 3));
+    assert_eq!(report.namespaces.iter().map(|n| n.roots).sum::<u64>(), 4);
+
+    let mut store = fork.store().clone();
+    let root = merkle_key("main", 3);
+    let mut record = store.cache[&root].clone();
+    *record.last_mut().unwrap() ^= 1;
+    store.cache.insert(root.clone(), record);
+    // A version committed above the current height.
+    let above = b"main-kw-01-00000000000000000005".to_vec();
+    let version = store.cache.range(b"main-kw-".to_vec()..).next().unwrap();
+    store.cache.insert(above.clone(), version.1.clone());
+    let corrupted = b"main-kw-00-00000000000000000001".to_vec();
+    store.cache.insert(corrupted.clone(), vec![1]);
+    let orphaned = b"lost-kw-00".to_vec();
+    store.cache.insert(orphaned.clone(), vec![]);
+    // Height 2 chains from the stored root of height 1.
+    let commit = b"main-cm-00000000000000000001".to_vec();
+    store.cache.remove(&commit);
+
+    let report = verify::<_, M>(&store)?;
+    let problems: std::vec::Vec<_> = report
+        .problems
+        .iter()
+        .map(|p| (p.key.clone(), p.kind.clone()))
+        .collect();
+    assert_eq!(problems.len(), 5, "{:?}", report.problems);
+    assert!(problems.contains(&(orphaned, ProblemKind::Orphaned)));
+    assert!(problems.contains(&(commit, ProblemKind::MissingCommit { height: 1 })));
+    assert!(problems.contains(&(root, ProblemKind::RootMismatch { height: 3 })));
+    assert!(problems.contains(&(
+        above,
+        ProblemKind::AboveHeight {
+            height: 5,
+            current: 3
+        }
+    )));
+    assert!(problems
+        .iter()
+        .any(|(k, kind)| *k == corrupted && matches!(kind, ProblemKind::Corrupted { .. })));
+
+    // Deques hash their head and tail after the entries.
+    let d = Deque::<u32>::default();
+    let mut dq = SnapshotableStorage::<_, M, _>::new(d, MemoryBackend::new())?;
+    dq.push_back(1)?;
+    dq.push_front(0)?;
+    assert_eq!(dq.commit()?, 1);
+    dq.pop_back()?;
+    dq.push_back(2)?;
+    assert_eq!(dq.commit()?, 2);
+    let report = verify::<_, M>(dq.store())?;
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(report.namespaces[0].roots, 2);
+
+    // Versions left by rollback are stale.
+    let m = Map::<u32, u32>::default();
+    let mut ss = SnapshotableStorage::<_, M, _>::new(m, MemoryBackend::new())?;
+    ss.insert(1, 1)?;
+    assert_eq!(ss.commit()?, 1);
+    ss.insert(1, 2)?;
+    assert_eq!(ss.commit()?, 2);
+    ss.rollback(1)?;
+    let report = verify::<_, M>(ss.store())?;
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(
+        report.problems[0].kind,
+        ProblemKind::Stale {
+            height: 2,
+            current: 1
+        }
+    );
+
+    Ok(())
+}
+
 fn main() {
     let _ = map_mem_test();
     let _ = value_mem_test();
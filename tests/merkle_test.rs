@@ -1,8 +1,8 @@
 use bs3::backend::MemoryBackend;
 use bs3::merkle::{append_only, Merkle};
 use bs3::OperationBytes;
-use bs3::Result;
-use sha3::Sha3_256;
+use bs3::{Error, Result};
+use sha3::{Sha3_256, Sha3_512};
 
 #[test]
 fn test() -> Result<()> {
@@ -78,3 +78,16 @@ fn test() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn corrupted_root_test() -> Result<()> {
+    let mut s = MemoryBackend::new();
+    let mut merkle = append_only::AppendOnlyMerkle::<Sha3_256>::new("merkle_test", 0);
+    merkle.insert(&mut s, vec![].as_slice())?;
+
+    // A root of another digest size is reported, not copied.
+    let merkle = append_only::AppendOnlyMerkle::<Sha3_512>::new("merkle_test", 1);
+    assert!(matches!(merkle.root(&s), Err(Error::Corrupted { .. })));
+
+    Ok(())
+}
@@ -1,9 +1,9 @@
-use bs3::backend::{sled_db_open, SledBackend};
+use bs3::backend::{sled::Mode, sled_db_open, MemoryBackend, SledBackend, SledConfig};
 use bs3::merkle::empty::EmptyMerkle;
 use bs3::model::{DoubleKeyMap, Map, Value, Vec};
 use bs3::prelude::Tree;
-use bs3::{Cow, DoubleKeyMapStore, MapStore, Result, ValueStore, VecStore};
-use bs3::{SnapshotableStorage, Transaction};
+use bs3::{Cow, DoubleKeyMapStore, Error, MapStore, Result, ValueStore, VecStore};
+use bs3::{SharedStorage, SnapshotableStorage, Store, Transaction};
 use sha3::Sha3_512;
 
 fn sled_vec_test() -> Result<()> {
@@ -268,6 +268,177 @@ fn sled_doublekeymap_test_reload_and_callback(is_rollback: bool) -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn sled_config_test() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("bs3_config_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    let cfg = SledConfig::new()
+        .path(&path)
+        .mode(Mode::LowSpace)
+        .cache_capacity(1_000_000)
+        .flush_every_ms(None)
+        .compression_factor(Some(3));
+    {
+        let db = cfg.open()?;
+        let s = SledBackend::open_tree(&db, "config_sled_test")?;
+        let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+        assert_eq!(ss.insert(1, 1)?, None);
+        assert_eq!(ss.commit()?, 1);
+        db.flush()?;
+    }
+
+    let db = cfg.clone().read_only(true).open()?;
+    let s = SledBackend::open_tree_read_only(&db, "config_sled_test")?;
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+    assert_eq!(ss.height, 1);
+    assert_eq!(ss.get(&1)?, Some(Cow::Owned(1)));
+    assert!(matches!(ss.commit(), Err(Error::ReadOnly)));
+    drop(ss);
+    let mut s = SledBackend::open_tree_read_only(&db, "config_sled_test")?;
+    assert!(matches!(
+        s.execute(vec![(b"k".to_vec(), b"v".to_vec())]),
+        Err(Error::ReadOnly)
+    ));
+    assert!(matches!(
+        SledBackend::open_tree_read_only(&db, "missing_tree"),
+        Err(Error::BackendIo(_))
+    ));
+    assert!(!db.tree_names().iter().any(|name| name == b"missing_tree"));
+    drop(db);
+
+    let missing = SledConfig::new().path(path.join("missing")).read_only(true);
+    assert!(matches!(missing.open(), Err(Error::BackendIo(_))));
+
+    std::fs::remove_dir_all(&path).unwrap();
+    Ok(())
+}
+
+#[test]
+fn sled_online_backup_test() -> Result<()> {
+    let db = sled_db_open(None).unwrap();
+    let s = SledBackend::open_tree(&db, "online_backup_sled_test")?;
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+    for i in 0..100 {
+        ss.insert(i, i)?;
+    }
+    assert_eq!(ss.commit()?, 1);
+
+    // The view shares the backend, source keeps committing during backup.
+    let view = ss.at(1)?;
+    let backup = std::thread::spawn(move || {
+        let mut backup = MemoryBackend::new();
+        view.backup_to(&mut backup, 1)
+            .map(|_| backup)
+            .map_err(|e| e.to_string())
+    });
+    for i in 0..100 {
+        ss.insert(i, i + 1)?;
+        ss.commit()?;
+    }
+    let backup = backup.join().unwrap().unwrap();
+
+    let copy = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), backup)?;
+    assert_eq!(copy.height, 1);
+    assert_eq!(copy.get(&99)?, Some(Cow::Owned(99)));
+
+    Ok(())
+}
+
+#[test]
+fn sled_shared_readers_test() -> Result<()> {
+    let db = sled_db_open(None).unwrap();
+    let s = SledBackend::open_tree(&db, "shared_readers_sled_test")?;
+    let ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+    let mut shared = SharedStorage::new(ss)?;
+    shared.insert(0_u32, 0_u32)?;
+    assert_eq!(shared.commit()?, 1);
+
+    let reader = shared.reader();
+    let pinned = reader.snapshot();
+    let readers: std::vec::Vec<_> = (0..4)
+        .map(|_| {
+            let reader = reader.clone();
+            std::thread::spawn(move || -> std::result::Result<(), String> {
+                for _ in 0..50 {
+                    // Each height holds `height - 1` under all keys written so far.
+                    let snapshot = reader.snapshot();
+                    let expected = snapshot.height as u32 - 1;
+                    let value = snapshot.get(&0).map_err(|e| e.to_string())?;
+                    if value.map(|v| *v) != Some(expected) {
+                        return Err(format!("torn read at height {}", snapshot.height));
+                    }
+                }
+                Ok(())
+            })
+        })
+        .collect();
+
+    for height in 1..50_u32 {
+        shared.insert(0, height)?;
+        shared.insert(height, height)?;
+        shared.commit()?;
+    }
+    for handle in readers {
+        handle.join().unwrap().unwrap();
+    }
+
+    assert_eq!(reader.height(), 50);
+    assert_eq!(pinned.height, 1);
+    assert_eq!(pinned.get(&0)?, Some(Cow::Owned(0)));
+    assert_eq!(pinned.get(&1)?, None);
+    assert_eq!(reader.snapshot_at(10)?.get(&0)?, Some(Cow::Owned(9)));
+
+    let stale = reader.snapshot_at(30)?;
+    shared.rollback(20)?;
+    assert_eq!(reader.height(), 20);
+    assert_eq!(reader.snapshot().get(&30)?, None);
+    assert_eq!(stale.get(&0)?, Some(Cow::Owned(29)));
+    assert_eq!(stale.get(&25)?, Some(Cow::Owned(25)));
+
+    // Pruning removes the versions stale snapshots read.
+    let s = SledBackend::open_tree(&db, "shared_readers_pruning_sled_test")?;
+    let ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+    let mut shared = SharedStorage::new(ss.with_pruning())?;
+    for height in 0..4_u32 {
+        shared.insert(0, height)?;
+        shared.insert(height, height)?;
+        shared.commit()?;
+    }
+    let reader = shared.reader();
+    let stale = reader.snapshot();
+    assert_eq!(stale.height, 4);
+    shared.rollback(2)?;
+    assert_eq!(stale.get(&0)?, Some(Cow::Owned(1)));
+    assert_eq!(stale.get(&3)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn sled_tx_metrics_test() -> Result<()> {
+    let db = sled_db_open(None).unwrap();
+    let s = SledBackend::open_tree(&db, "tx_metrics_sled_test")?;
+    let mut ss = SnapshotableStorage::<_, EmptyMerkle<Sha3_512>, _>::new(Map::default(), s)?;
+    ss.insert(1_u32, 1_u32)?;
+    assert_eq!(ss.commit()?, 1);
+
+    // Readers on other threads share the counts of the transaction.
+    let tx = Transaction::new(&ss);
+    std::thread::scope(|scope| {
+        let handles: std::vec::Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| tx.get(&1).map(|v| v.map(|v| *v))))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), Some(1));
+        }
+    });
+    assert_eq!(tx.metrics().lookups, 4);
+    assert_eq!(tx.metrics().keys_touched, 1);
+
+    Ok(())
+}
+
 fn main() {
     let _ = sled_vec_test_reload_and_callback(false);
     let _ = sled_vec_test_reload_and_callback(true);